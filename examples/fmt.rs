@@ -0,0 +1,34 @@
+use std::{fs, error::Error};
+use ascesis::{Formatter, FormatterConfig};
+
+/// Prints a script formatted with a custom [`FormatterConfig`].  For
+/// formatting files in place, use `ascesis fmt`.
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = clap::App::new("Fmt")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("Ascesis formatter demo")
+        .args_from_usage(
+            "<FILE>                    'script to format'
+             --max-width=[WIDTH]       'maximum line width (default: 100)'
+             --tab-spaces=[SPACES]     'number of spaces per indentation level (default: 4)'",
+        )
+        .get_matches();
+
+    let mut config = FormatterConfig::new();
+
+    if let Some(width) = args.value_of("max-width") {
+        config = config.with_max_width(width.parse()?);
+    }
+
+    if let Some(spaces) = args.value_of("tab-spaces") {
+        config = config.with_tab_spaces(spaces.parse()?);
+    }
+
+    let formatter = Formatter::new().with_config(config);
+    let script = fs::read_to_string(args.value_of("FILE").unwrap())?;
+
+    print!("{}", formatter.format(&script)?);
+
+    Ok(())
+}
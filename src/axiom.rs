@@ -1,14 +1,16 @@
 use std::{fmt, str::FromStr};
 use regex::Regex;
 use crate::ascesis_parser::{
-    CesFileParser, CesFileBlockParser, ImmediateDefParser, CesImmediateParser, CesInstanceParser,
-    PropBlockParser, CapsBlockParser, UnboundedBlockParser, WeightsBlockParser, InhibitBlockParser,
-    WeightlessBlockParser, RexParser, ThinArrowRuleParser, FatArrowRuleParser, PolynomialParser,
+    CesFileParser, CesFileBlockParser, ImmediateDefParser, TemplateDefParser, CesImmediateParser,
+    CesInstanceParser, PropBlockParser, CapsBlockParser, UnboundedBlockParser, WeightsBlockParser,
+    InhibitBlockParser, WeightlessBlockParser, RexParser, ThinArrowRuleParser, FatArrowRuleParser,
+    PolynomialParser,
 };
 use crate::{
-    CesFile, CesFileBlock, ImmediateDef, CesImmediate, CesInstance, PropBlock, CapacitiesBlock,
-    UnboundedBlock, WeightsBlock, InhibitorsBlock, WeightlessBlock, Rex, ThinArrowRule,
-    FatArrowRule, Polynomial, Lexer, AscesisError, AscesisErrorKind, error::ParserError,
+    CesFile, CesFileBlock, ImmediateDef, TemplateDef, CesImmediate, CesInstance, PropBlock,
+    CapacitiesBlock, UnboundedBlock, WeightsBlock, InhibitorsBlock, WeightlessBlock, Rex,
+    ThinArrowRule, FatArrowRule, Polynomial, Lexer, AscesisError, AscesisErrorKind,
+    error::ParserError,
 };
 
 #[derive(Clone, Debug)]
@@ -19,8 +21,8 @@ impl Axiom {
         let symbol = symbol.as_ref();
 
         match symbol {
-            "CesFileBlock" | "ImmediateDef" | "CesImmediate" | "CesInstance" | "PropBlock"
-            | "CapsBlock" | "UnboundedBlock" | "WeightsBlock" | "InhibitBlock"
+            "CesFileBlock" | "ImmediateDef" | "TemplateDef" | "CesImmediate" | "CesInstance"
+            | "PropBlock" | "CapsBlock" | "UnboundedBlock" | "WeightsBlock" | "InhibitBlock"
             | "ActivateBlock" | "DropBlock" | "Rex" | "ThinArrowRule" | "FatArrowRule"
             | "Polynomial" => Some(Axiom(symbol.to_owned())),
             _ => None,
@@ -30,6 +32,7 @@ impl Axiom {
     pub fn guess_from_phrase<S: AsRef<str>>(phrase: S) -> Self {
         lazy_static! {
             static ref IMM_RE: Regex = Regex::new(r"^ces\s+[[:alpha:]][[:word:]]*\s*\{").unwrap();
+            static ref TMP_RE: Regex = Regex::new(r"^ces\s+[[:alpha:]][[:word:]]*\s*\(").unwrap();
             static ref VIS_RE: Regex = Regex::new(r"^vis\s*\{").unwrap();
             static ref SAT_RE: Regex = Regex::new(r"^sat\s*\{").unwrap();
             static ref CAPS_RE: Regex = Regex::new(r"^caps\s*\{").unwrap();
@@ -38,9 +41,13 @@ impl Axiom {
             static ref INHIBIT_RE: Regex = Regex::new(r"^inhibit\s*\{").unwrap();
             static ref ACTIVATE_RE: Regex = Regex::new(r"^activate\s*\{").unwrap();
             static ref DROP_RE: Regex = Regex::new(r"^drop\s*\{").unwrap();
-            static ref TIN_RE: Regex = Regex::new(r"^[[:alpha:]][[:word:]]*\s*!\s*\(").unwrap();
-            static ref IIN_RE: Regex =
-                Regex::new(r"^[[:alpha:]][[:word:]]*\s*\(\s*\)\s*$").unwrap();
+            static ref TIN_RE: Regex =
+                Regex::new(r"^([[:alpha:]][[:word:]]*\s*::\s*)?[[:alpha:]][[:word:]]*\s*!\s*\(")
+                    .unwrap();
+            static ref IIN_RE: Regex = Regex::new(
+                r"^([[:alpha:]][[:word:]]*\s*::\s*)?[[:alpha:]][[:word:]]*\s*\(\s*\)\s*$"
+            )
+            .unwrap();
             static ref REX_RE: Regex = Regex::new(r"(\{|,|!|\(\s*\))").unwrap();
             static ref TAR_RE: Regex = Regex::new(r"(->|<-)").unwrap();
             static ref FAR_RE: Regex = Regex::new(r"(=>|<=)").unwrap();
@@ -50,6 +57,8 @@ impl Axiom {
 
         if IMM_RE.is_match(phrase) {
             Axiom("ImmediateDef".to_owned())
+        } else if TMP_RE.is_match(phrase) {
+            Axiom("TemplateDef".to_owned())
         } else if VIS_RE.is_match(phrase) || SAT_RE.is_match(phrase) {
             Axiom("PropBlock".to_owned())
         } else if CAPS_RE.is_match(phrase) {
@@ -98,6 +107,7 @@ impl Axiom {
         match self.0.as_str() {
             "CesFileBlock" => from_phrase_as!(CesFileBlock, phrase),
             "ImmediateDef" => from_phrase_as!(ImmediateDef, phrase),
+            "TemplateDef" => from_phrase_as!(TemplateDef, phrase),
             "CesImmediate" => from_phrase_as!(CesImmediate, phrase),
             "CesInstance" => from_phrase_as!(CesInstance, phrase),
             "PropBlock" => from_phrase_as!(PropBlock, phrase),
@@ -135,7 +145,13 @@ macro_rules! impl_from_phrase_for {
                     err.map_token(|t| format!("{}", t)).map_error(|e| e.to_owned())
                 })?;
 
-                Ok(result)
+                // A phrase is accepted only if no syntax error had to
+                // be recovered from.
+                if let Some(recovery) = errors.into_iter().next() {
+                    Err(recovery.error.map_token(|t| format!("{}", t)).map_error(|e| e.to_owned()))
+                } else {
+                    Ok(result)
+                }
             }
         }
     };
@@ -144,6 +160,7 @@ macro_rules! impl_from_phrase_for {
 impl_from_phrase_for!(CesFile, CesFileParser);
 impl_from_phrase_for!(CesFileBlock, CesFileBlockParser);
 impl_from_phrase_for!(ImmediateDef, ImmediateDefParser);
+impl_from_phrase_for!(TemplateDef, TemplateDefParser);
 impl_from_phrase_for!(CesImmediate, CesImmediateParser);
 impl_from_phrase_for!(CesInstance, CesInstanceParser);
 impl_from_phrase_for!(PropBlock, PropBlockParser);
@@ -172,6 +189,7 @@ macro_rules! impl_from_str_for {
 impl_from_str_for!(CesFile);
 impl_from_str_for!(CesFileBlock);
 impl_from_str_for!(ImmediateDef);
+impl_from_str_for!(TemplateDef);
 impl_from_str_for!(CesImmediate);
 impl_from_str_for!(CesInstance);
 impl_from_str_for!(PropBlock);
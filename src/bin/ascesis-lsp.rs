@@ -0,0 +1,56 @@
+use std::io;
+use fern::colors::{Color, ColoredLevelConfig};
+use ascesis::lsp::LanguageServer;
+
+fn setup_logger(verbosity: u64) {
+    let log_level = match verbosity {
+        0 => log::LevelFilter::Error,
+        1 => log::LevelFilter::Warn,
+        2 => log::LevelFilter::Info,
+        3 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+
+    let colors = ColoredLevelConfig::new()
+        .trace(Color::Blue)
+        .debug(Color::Yellow)
+        .info(Color::Green)
+        .warn(Color::Magenta)
+        .error(Color::Red);
+
+    // Standard output is reserved for the protocol, hence all logging
+    // goes to standard error.
+    let console_logger = fern::Dispatch::new()
+        .format(move |out, message, record| {
+            out.finish(format_args!("[{}]\t{}.", colors.color(record.level()), message))
+        })
+        .level(log_level)
+        .chain(io::stderr());
+
+    fern::Dispatch::new()
+        .chain(console_logger)
+        .apply()
+        .unwrap_or_else(|err| eprintln!("[ERROR] {}.", err));
+}
+
+fn main() {
+    let args = clap::App::new("ascesis-lsp")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("Ascesis language server, communicating over standard input and output")
+        .args_from_usage("-v, --verbose... 'level of log messages written to standard error'")
+        .get_matches();
+
+    setup_logger(args.occurrences_of("verbose"));
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut server = LanguageServer::new();
+
+    if let Err(err) = server.run(stdin.lock(), stdout.lock()) {
+        log::error!("{}", err);
+        std::process::exit(1);
+    }
+
+    std::process::exit(server.get_exit_code());
+}
@@ -1,4 +1,10 @@
-use std::{ops::Deref, fmt, error::Error};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    ops::Deref,
+    fmt, fs,
+    error::Error,
+};
 use log::Level::Debug;
 use aces::{
     Content, PartialContent, Compilable, CompilableMut, CompilableAsContent,
@@ -6,34 +12,480 @@ use aces::{
 };
 use crate::{
     PropBlock, PropSelector, CapacitiesBlock, UnboundedBlock, WeightsBlock, InhibitorsBlock,
-    WeightlessBlock, Rex, Lexer, AscesisError, AscesisErrorKind, ascesis_parser::CesFileParser,
+    WeightlessBlock, Rex, TemplateDef, ArgValue, DotName, ToDotName, Literal, Lexer, Token, Span,
+    AscesisError, AscesisErrorKind, AscesisWarning, AscesisWarningKind, IncoherentLink,
+    ascesis_parser::CesFileParser, spelling::find_similar, equivalence::get_dot_polynomials,
+    coherence::find_incoherent_links, span::NodeSpan,
 };
 
 #[derive(Default, Debug)]
 pub struct CesFile {
     script:        Option<String>,
+    path:          Option<PathBuf>,
+    namespace:     Option<String>,
     blocks:        Vec<CesFileBlock>,
     root_block_id: Option<usize>,
     root_content:  Option<PartialContent>,
-    modules:       Vec<PartialContent>,
+    modules:       Vec<CesFile>,
+    instances:     Vec<ImmediateDef>,
+    local_blocks:  Vec<CesFileBlock>,
 }
 
 impl CesFile {
     pub fn from_script<S: AsRef<str>>(script: S) -> Result<Self, Box<dyn Error>> {
+        match Self::from_script_recovering(script)? {
+            (result, None) => Ok(result),
+            (_, Some(err)) => Err(err.into()),
+        }
+    }
+
+    /// Parses `script`, recovering from syntax errors in blocks, in
+    /// bodies of structure definitions, in rule expressions between
+    /// braces, and in fields of context blocks.
+    ///
+    /// Returns the file made of all well-formed parts of `script`,
+    /// together with the error describing everything recovered from,
+    /// if anything was.  Each part which failed to parse is replaced
+    /// with an empty one, and a block which failed to parse as a whole
+    /// becomes [`CesFileBlock::Bad`].  Fails only if the parser can't
+    /// recover, e.g. at the end of an unterminated block.
+    pub fn from_script_recovering<S: AsRef<str>>(
+        script: S,
+    ) -> Result<(Self, Option<AscesisError>), Box<dyn Error>> {
         let script = script.as_ref();
         let mut errors = Vec::new();
         let lexer = Lexer::new(script);
         match CesFileParser::new().parse(&mut errors, lexer) {
             Ok(mut result) => {
+                result.script = Some(script.to_owned());
+
                 if errors.is_empty() {
-                    result.script = Some(script.to_owned());
+                    Ok((result, None))
+                } else {
+                    Ok((result, Some(AscesisErrorKind::from(errors).with_script(script))))
+                }
+            }
+            Err(err) => Err(AscesisErrorKind::from(err).with_script(script).into()),
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let script = fs::read_to_string(path)?;
+        let mut result = Self::from_script(script)?;
+
+        result.set_path(path);
+
+        Ok(result)
+    }
+
+    /// Sets the path of the file this script was read from.  Paths in
+    /// `use` directives are resolved relative to it.
+    pub fn set_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.path = Some(path.as_ref().to_path_buf());
+    }
+
+    #[inline]
+    pub fn get_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns all blocks of this file, in order of their appearance.
+    #[inline]
+    pub fn get_blocks(&self) -> &[CesFileBlock] {
+        self.blocks.as_slice()
+    }
+
+    /// Returns all files imported, directly, by this file.  These are
+    /// loaded only when compiling.
+    #[inline]
+    pub fn get_modules(&self) -> &[CesFile] {
+        self.modules.as_slice()
+    }
+
+    /// Returns the namespace qualifying names of all definitions of
+    /// this file, if this is an imported file.
+    ///
+    /// The namespace of an imported file is the stem of its path,
+    /// e.g. `arrows` for `lib/arrows.ces`.
+    #[inline]
+    pub fn get_namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// Returns names and documentation of all structures defined in
+    /// this file, both immediate and template definitions, in order of
+    /// their appearance.  Undocumented structures are listed as well.
+    ///
+    /// Definitions of imported files aren't included, but these may be
+    /// listed through [`get_modules()`](CesFile::get_modules), after
+    /// compilation.
+    pub fn get_docs(&self) -> Vec<(&CesName, Option<&str>)> {
+        self.blocks
+            .iter()
+            .filter_map(|block| match block {
+                CesFileBlock::Imm(def) => Some((def.get_name(), def.get_doc())),
+                CesFileBlock::Template(def) => Some((def.get_name(), def.get_doc())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Replaces [`DotName`]s found in `dot_map` keys with the
+    /// corresponding values, in all structure definitions and context
+    /// blocks of this file.  In template definitions, dots named after
+    /// template arguments aren't renamed.
+    ///
+    /// This is meant to be called before compilation.  Definitions and
+    /// context blocks of imported files aren't renamed.
+    ///
+    /// Returns a warning for each capacity, weight or label dropped,
+    /// because it was given to a dot which became equal to another
+    /// after renaming.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) -> Vec<AscesisWarning> {
+        self.blocks.iter_mut().flat_map(|block| block.rename_dots(dot_map)).collect()
+    }
+
+    /// Returns warnings raised while parsing this file, i.e.
+    /// applications of idempotency laws in arrow rules, blocks ignored
+    /// because of errors, dots of context blocks not used in any
+    /// structure, and unknown keys of property blocks, in order of
+    /// their appearance.
+    ///
+    /// Warnings of imported files aren't included.  Unused dots are
+    /// reported only after imported files are loaded, i.e. after
+    /// compilation, if this file has any `use` directives.
+    pub fn get_warnings(&self) -> Vec<AscesisWarning> {
+        let mut warnings = Vec::new();
+        let has_imports = self.blocks.iter().any(|block| matches!(block, CesFileBlock::Use(_)));
+        let used_dots = if has_imports && self.modules.is_empty() {
+            None
+        } else {
+            Some(self.get_used_dot_names())
+        };
+
+        for block in self.blocks.iter() {
+            if let Some(ref used_dots) = used_dots {
+                for dot_name in block.get_dot_names() {
+                    if !used_dots.contains(dot_name) {
+                        let similar = find_similar(dot_name, used_dots.iter().copied());
+                        let kind = AscesisWarningKind::DotUnused(
+                            dot_name.to_owned(),
+                            similar.map(Into::into),
+                        );
+
+                        warnings.push(self.locate_warning(kind, dot_name, block.get_span()));
+                    }
+                }
+            }
+
+            match block {
+                CesFileBlock::Imm(def) => warnings.extend(def.rex.get_warnings()),
+                CesFileBlock::Template(def) => warnings.extend(def.rex.get_warnings()),
+                CesFileBlock::Vis(blk) | CesFileBlock::SAT(blk) => {
+                    for (key, similar) in blk.get_unknown_keys() {
+                        let kind = AscesisWarningKind::PropKeyUnknown(
+                            blk.get_selector().unwrap_or_default(),
+                            key.to_owned(),
+                            similar.map(Into::into),
+                        );
+
+                        warnings.push(self.locate_warning(kind, key, Some(blk.get_span())));
+                    }
+                }
+                CesFileBlock::Bad(err) => {
+                    let warning = AscesisWarning::from(AscesisWarningKind::BlockIgnored(
+                        err.get_kind().to_string(),
+                    ));
+
+                    warnings.push(if let Some(span) = err.get_span() {
+                        warning.with_default_span(span)
+                    } else {
+                        warning
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        warnings
+    }
+
+    /// Returns names of all dots used in structure definitions of this
+    /// file and of all imported files.
+    fn get_used_dot_names(&self) -> BTreeSet<&str> {
+        let mut dot_names = BTreeSet::new();
+
+        for block in self.blocks.iter() {
+            match block {
+                CesFileBlock::Imm(def) => dot_names.extend(def.rex.get_dot_names()),
+                CesFileBlock::Template(def) => dot_names.extend(def.rex.get_dot_names()),
+                _ => {}
+            }
+        }
+
+        for module in self.modules.iter() {
+            dot_names.extend(module.get_used_dot_names());
+        }
 
-                    Ok(result)
+        dot_names
+    }
+
+    /// Creates a warning spanning the first occurrence of identifier
+    /// `name` within `span`, or the whole `span`, if not found.
+    fn locate_warning(
+        &self,
+        kind: AscesisWarningKind,
+        name: &str,
+        span: Option<Span>,
+    ) -> AscesisWarning {
+        let warning = AscesisWarning::from(kind);
+
+        if let Some(span) = span {
+            let name_span = self
+                .script
+                .as_deref()
+                .and_then(|script| script.get(span.start()..span.end()))
+                .and_then(|text| {
+                    Lexer::new(text).filter_map(Result::ok).find_map(|(start, token, end)| {
+                        match token {
+                            Token::Identifier(identifier) if identifier == name => {
+                                Some(Span::new(span.start() + start, span.start() + end))
+                            }
+                            _ => None,
+                        }
+                    })
+                });
+
+            warning.with_default_span(name_span.unwrap_or(span))
+        } else {
+            warning
+        }
+    }
+
+    fn with_script(&self, kind: AscesisErrorKind) -> AscesisError {
+        error_in_script(self.script.as_deref(), kind)
+    }
+
+    /// Attaches `span` and the script of this file to `err`, unless
+    /// `err` already has them, or `err` isn't an [`AscesisError`].
+    ///
+    /// Empty `span` leaves `err` intact, because it indicates a
+    /// construct which wasn't parsed from this file, e.g. a template
+    /// instantiation.
+    fn locate_error(&self, err: Box<dyn Error>, span: Span) -> Box<dyn Error> {
+        if span.is_empty() {
+            return err
+        }
+
+        match err.downcast::<AscesisError>() {
+            Ok(err) => err.with_span(span).with_default_script(self.script.as_deref()).into(),
+            Err(err) => err,
+        }
+    }
+
+    /// Loads all files imported by this file, recursively.
+    ///
+    /// The `stack` holds canonical paths of all importing files, which
+    /// is used for detecting import cycles.  The `namespaces` map each
+    /// namespace taken so far to the canonical path of the file it was
+    /// taken by, since two different files may not share a namespace.
+    fn load_modules(
+        &mut self,
+        stack: &mut Vec<PathBuf>,
+        namespaces: &mut BTreeMap<String, PathBuf>,
+    ) -> Result<(), Box<dyn Error>> {
+        let base_dir = self.path.as_deref().and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+        let mut modules = Vec::new();
+
+        for block in self.blocks.iter() {
+            if let CesFileBlock::Use(directive) = block {
+                let path = fs::canonicalize(base_dir.join(&directive.path)).map_err(|err| {
+                    self.with_script(AscesisErrorKind::ImportFailure(
+                        directive.path.clone(),
+                        err.to_string(),
+                        *directive.span,
+                    ))
+                })?;
+
+                if let Some(pos) = stack.iter().position(|p| *p == path) {
+                    let mut cycle: Vec<String> =
+                        stack[pos..].iter().map(|p| p.display().to_string()).collect();
+
+                    cycle.push(path.display().to_string());
+
+                    return Err(self
+                        .with_script(AscesisErrorKind::ImportCycle(cycle, *directive.span))
+                        .into())
+                }
+
+                let namespace = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                if let Some(other_path) = namespaces.get(&namespace) {
+                    if *other_path != path {
+                        return Err(self
+                            .with_script(AscesisErrorKind::NamespaceConflict(
+                                namespace,
+                                vec![directive.path.clone(), other_path.display().to_string()],
+                                *directive.span,
+                            ))
+                            .into())
+                    }
                 } else {
-                    Err(AscesisErrorKind::from(errors).with_script(script.to_owned()).into())
+                    namespaces.insert(namespace.clone(), path.clone());
+                }
+
+                info!("Importing \"{}\"", path.display());
+
+                let mut module = CesFile::from_path(&path)?;
+
+                module.namespace = Some(namespace);
+
+                stack.push(path);
+                module.load_modules(stack, namespaces)?;
+                stack.pop();
+
+                modules.push(module);
+            }
+        }
+
+        self.modules = modules;
+
+        Ok(())
+    }
+
+    /// Adds names of all definitions of this file to the tables of
+    /// imported names.  Definitions this file imports itself aren't
+    /// exported.
+    fn export_names(&self, structures: &mut NameTable, templates: &mut NameTable) {
+        for block in self.blocks.iter() {
+            match block {
+                CesFileBlock::Imm(imm) => structures.insert_imported(imm.name.clone()),
+                CesFileBlock::Template(template) => {
+                    templates.insert_imported(template.name.clone())
                 }
+                _ => {}
+            }
+        }
+    }
+
+    /// Qualifies names of all definitions of an imported file with its
+    /// namespace, and resolves all names referenced in definitions.
+    ///
+    /// An unqualified name refers to the definition of that name in
+    /// the same file, if there is one, or else to the only imported
+    /// definition of that name.
+    ///
+    /// Errors are pushed to `errors`, and resolution goes on with the
+    /// next definition.
+    fn resolve_names(&mut self, errors: &mut Vec<AscesisError>) {
+        for module in self.modules.iter_mut() {
+            module.resolve_names(errors);
+        }
+
+        let mut structures = NameTable::default();
+        let mut templates = NameTable::default();
+
+        for module in self.modules.iter() {
+            module.export_names(&mut structures, &mut templates);
+        }
+
+        let namespace = self.namespace.as_deref();
+        let script = self.script.as_deref();
+        let mut first_spans = BTreeMap::new();
+
+        for block in self.blocks.iter() {
+            let (name, span, is_template) = match block {
+                CesFileBlock::Imm(imm) => {
+                    structures.insert_local(&imm.name, namespace);
+                    (&imm.name, imm.span, false)
+                }
+                CesFileBlock::Template(template) => {
+                    templates.insert_local(&template.name, namespace);
+                    (&template.name, template.get_span(), true)
+                }
+                _ => continue,
+            };
+
+            // Structures and templates are instantiated differently,
+            // hence a structure and a template may share a name.
+            if let Some(&first_span) = first_spans.get(&(name, is_template)) {
+                let kind = if is_template {
+                    AscesisErrorKind::TemplateRedefined(name.to_string(), first_span)
+                } else {
+                    AscesisErrorKind::StructureRedefined(name.to_string(), first_span)
+                };
+
+                errors.push(error_in_script(script, kind).with_span(span));
+            } else {
+                first_spans.insert((name, is_template), span);
+            }
+        }
+
+        // Parameter types of all templates in scope, by qualified name.
+        let mut arg_types = BTreeMap::new();
+
+        for module in self.modules.iter() {
+            for block in module.blocks.iter() {
+                if let CesFileBlock::Template(template) = block {
+                    arg_types.insert(template.name.clone(), template.get_arg_types());
+                }
+            }
+        }
+
+        for block in self.blocks.iter() {
+            if let CesFileBlock::Template(template) = block {
+                if let Some(name) = templates.local.get(&template.name) {
+                    arg_types.insert(name.clone(), template.get_arg_types());
+                }
+            }
+        }
+
+        for block in self.blocks.iter_mut() {
+            match block {
+                CesFileBlock::Imm(imm) => {
+                    if let Some(name) = structures.local.get(&imm.name) {
+                        imm.name = name.clone();
+                    }
+
+                    if let Err(kind) = imm.rex.resolve_names(
+                        |name, span| structures.resolve(name, span),
+                        |name, span| templates.resolve(name, span),
+                        &arg_types,
+                    ) {
+                        errors.push(error_in_script(script, kind));
+                    }
+                }
+                CesFileBlock::Template(template) => {
+                    if let Some(name) = templates.local.get(&template.name) {
+                        template.name = name.clone();
+                    }
+
+                    let params: BTreeSet<CesName> = template
+                        .get_args()
+                        .iter()
+                        .map(|arg| arg.get_name().to_ces_name())
+                        .collect();
+
+                    if let Err(kind) = template.rex.resolve_names(
+                        |name, span| {
+                            if params.contains(name) {
+                                Ok(None)
+                            } else {
+                                structures.resolve(name, span)
+                            }
+                        },
+                        |name, span| templates.resolve(name, span),
+                        &arg_types,
+                    ) {
+                        errors.push(error_in_script(script, kind));
+                    }
+                }
+                _ => {}
             }
-            Err(err) => Err(AscesisErrorKind::from(err).with_script(script.to_owned()).into()),
         }
     }
 
@@ -45,13 +497,13 @@ impl CesFile {
         for (ndx, block) in self.blocks.iter().enumerate() {
             if let CesFileBlock::Imm(imm) = block {
                 if imm.name.0.as_str() == root_name {
-                    if self.root_block_id.is_none() {
-                        self.root_block_id = Some(ndx);
+                    if let Some(first_ndx) = self.root_block_id {
+                        let first_span = self.blocks[first_ndx].get_span().unwrap_or_default();
+                        let kind = AscesisErrorKind::RootRedefined(root_name.into(), first_span);
+
+                        return Err(self.with_script(kind).with_span(imm.span).into())
                     } else {
-                        return Err(AscesisError::from(AscesisErrorKind::RootRedefined(
-                            root_name.into(),
-                        ))
-                        .into())
+                        self.root_block_id = Some(ndx);
                     }
                 }
             }
@@ -60,7 +512,15 @@ impl CesFile {
         if self.root_block_id.is_some() {
             Ok(())
         } else {
-            Err(AscesisError::from(AscesisErrorKind::RootMissing(root_name.into())).into())
+            let similar = find_similar_name(
+                &root_name.to_ces_name(),
+                self.blocks.iter().filter_map(|block| match block {
+                    CesFileBlock::Imm(imm) => Some(&imm.name),
+                    _ => None,
+                }),
+            );
+
+            Err(AscesisError::from(AscesisErrorKind::RootMissing(root_name.into(), similar)).into())
         }
     }
 
@@ -92,6 +552,146 @@ impl CesFile {
         }
     }
 
+    /// Returns the template of a given name, defined in this file or,
+    /// if not found, in an imported file.
+    fn get_template(&self, name: &CesName) -> Option<&TemplateDef> {
+        self.blocks
+            .iter()
+            .find_map(|block| match block {
+                CesFileBlock::Template(template) if template.get_name() == name => Some(template),
+                _ => None,
+            })
+            .or_else(|| self.modules.iter().find_map(|module| module.get_template(name)))
+    }
+
+    /// Returns names of all templates defined in this file and in
+    /// imported files.
+    fn get_template_names(&self) -> Vec<&CesName> {
+        self.blocks
+            .iter()
+            .filter_map(|block| match block {
+                CesFileBlock::Template(template) => Some(template.get_name()),
+                _ => None,
+            })
+            .chain(self.modules.iter().flat_map(|module| module.get_template_names()))
+            .collect()
+    }
+
+    /// Returns `true` if `name` is the name of an immediate definition
+    /// in this file or in an imported file.
+    fn defines_structure(&self, name: &CesName) -> bool {
+        self.blocks.iter().any(|block| matches!(block, CesFileBlock::Imm(imm) if &imm.name == name))
+            || self.modules.iter().any(|module| module.defines_structure(name))
+    }
+
+    /// Instantiates all templates used, directly or indirectly, in
+    /// immediate definitions.
+    ///
+    /// Each distinct instantiation becomes an [`ImmediateDef`] named
+    /// after the instantiating [`CesInstance`], and is then compiled
+    /// like any other immediate definition.  Local context blocks of
+    /// each instantiation are collected for compilation.
+    /// Instantiations already compiled in the context are skipped.
+    ///
+    /// Errors are pushed to `errors`, and instantiation goes on with
+    /// the next instance.  Returns names of all instances which failed
+    /// to instantiate.
+    fn instantiate_templates(
+        &mut self,
+        ctx: &ContextHandle,
+        errors: &mut Vec<AscesisError>,
+    ) -> BTreeSet<CesName> {
+        let mut failed = BTreeSet::new();
+        let mut visited: BTreeSet<CesName> =
+            self.instances.iter().map(|imm| imm.name.clone()).collect();
+        let mut pending: Vec<CesInstance> = Vec::new();
+
+        for block in self.blocks.iter() {
+            if let CesFileBlock::Imm(imm) = block {
+                pending.extend(imm.rex.get_instances().cloned());
+            }
+        }
+
+        while let Some(instance) = pending.pop() {
+            let instance_name = instance.get_instance_name();
+
+            if visited.insert(instance_name.clone()) {
+                if ctx.lock().unwrap().has_content(&instance_name) {
+                    continue
+                }
+
+                let result = self
+                    .get_template(&instance.name)
+                    .ok_or_else(|| {
+                        let similar = find_similar_name(&instance.name, self.get_template_names());
+
+                        AscesisError::from(AscesisErrorKind::TemplateMissing(
+                            instance.name.to_string(),
+                            similar,
+                        ))
+                        .with_span(*instance.span)
+                        .with_default_script(self.script.as_deref())
+                    })
+                    .and_then(|template| {
+                        template.instantiate(&instance, |name| {
+                            self.defines_structure(name) || ctx.lock().unwrap().has_content(name)
+                        })
+                    });
+
+                match result {
+                    Ok((imm, mut blocks)) => {
+                        pending.extend(imm.rex.get_instances().cloned());
+                        self.instances.push(imm);
+                        self.local_blocks.append(&mut blocks);
+                    }
+                    Err(err) => {
+                        errors.push(err);
+                        failed.insert(instance_name);
+                    }
+                }
+            }
+        }
+
+        failed
+    }
+
+    /// Returns all immediate definitions, including template
+    /// instantiations, ordered so that each definition follows all
+    /// definitions it depends on.
+    ///
+    /// Definitions on a dependency cycle, or depending on a structure
+    /// which is neither defined in this file nor already compiled in
+    /// the context, are left out, together with all definitions
+    /// depending on them, or on any of `failed_instances`.  An error
+    /// is pushed to `errors` for each cycle and each undefined
+    /// structure found.
+    fn get_compile_order(
+        &self,
+        ctx: &ContextHandle,
+        failed_instances: BTreeSet<CesName>,
+        errors: &mut Vec<AscesisError>,
+    ) -> Vec<&ImmediateDef> {
+        let mut sorter = DependencySorter { failed_instances, ..Default::default() };
+
+        let imms = self
+            .blocks
+            .iter()
+            .filter_map(|block| if let CesFileBlock::Imm(imm) = block { Some(imm) } else { None })
+            .chain(self.instances.iter());
+
+        for imm in imms.clone() {
+            sorter.defs.entry(&imm.name).or_insert(imm);
+        }
+
+        for imm in imms {
+            sorter.visit(imm, ctx);
+        }
+
+        errors.extend(sorter.errors.into_iter().map(|kind| self.with_script(kind)));
+
+        sorter.order
+    }
+
     fn get_content(&self) -> Result<&PartialContent, AscesisError> {
         if let Some(ref content) = self.root_content {
             Ok(content)
@@ -177,7 +777,9 @@ impl CesFile {
     pub fn get_sat_encoding(&self) -> Result<Option<sat::Encoding>, AscesisError> {
         for block in self.blocks.iter().rev() {
             if let CesFileBlock::SAT(blk) = block {
-                if let Some(encoding) = blk.get_sat_encoding()? {
+                if let Some(encoding) = blk.get_sat_encoding().map_err(|err| {
+                    err.with_span(blk.get_span()).with_default_script(self.script.as_deref())
+                })? {
                     return Ok(Some(encoding))
                 }
             }
@@ -189,7 +791,9 @@ impl CesFile {
     pub fn get_sat_search(&self) -> Result<Option<sat::Search>, AscesisError> {
         for block in self.blocks.iter().rev() {
             if let CesFileBlock::SAT(blk) = block {
-                if let Some(search) = blk.get_sat_search()? {
+                if let Some(search) = blk.get_sat_search().map_err(|err| {
+                    err.with_span(blk.get_span()).with_default_script(self.script.as_deref())
+                })? {
                     return Ok(Some(search))
                 }
             }
@@ -197,74 +801,185 @@ impl CesFile {
 
         Ok(None)
     }
+
+    /// Returns all links of the compiled root structure which are
+    /// specified on one side only, see [`IncoherentLink`].
+    ///
+    /// Each link points back to the first thin arrow rule specifying
+    /// it, found in the root structure or in structures it depends on,
+    /// including template instantiations.  Rules of structures defined
+    /// in imported files aren't searched.
+    pub fn get_incoherent_links(
+        &self,
+        ctx: &ContextHandle,
+    ) -> Result<Vec<IncoherentLink>, AscesisError> {
+        // Listing carrier ids requires mutable access, hence a copy.
+        let mut content = self.get_content()?.clone();
+        let dot_polys = get_dot_polynomials(&mut content, ctx);
+        let defs = self.get_root_dependencies()?;
+
+        let links = find_incoherent_links(&dot_polys)
+            .into_iter()
+            .map(|link| {
+                let origin = defs.iter().find_map(|imm| {
+                    imm.rex
+                        .get_thin_rules()
+                        .find(|rule| link.is_specified_by(rule))
+                        .map(|rule| (imm, rule))
+                });
+
+                if let Some((imm, rule)) = origin {
+                    link.with_origin(imm.name.clone(), rule.clone())
+                } else {
+                    link
+                }
+            })
+            .collect();
+
+        Ok(links)
+    }
+
+    /// Returns the root structure followed by all immediate
+    /// definitions, including template instantiations, it depends on,
+    /// directly or indirectly, in order of discovery.
+    fn get_root_dependencies(&self) -> Result<Vec<&ImmediateDef>, AscesisError> {
+        let imms: Vec<_> = self
+            .blocks
+            .iter()
+            .filter_map(|block| if let CesFileBlock::Imm(imm) = block { Some(imm) } else { None })
+            .chain(self.instances.iter())
+            .collect();
+        let mut result = vec![self.get_root()?];
+        let mut pos = 0;
+
+        while let Some(imm) = result.get(pos) {
+            for (name, _) in imm.rex.get_dependencies() {
+                if let Some(dep) = imms.iter().find(|dep| dep.name == name) {
+                    if !result.iter().any(|imm| imm.name == name) {
+                        result.push(dep);
+                    }
+                }
+            }
+            pos += 1;
+        }
+
+        Ok(result)
+    }
 }
 
-impl CompilableMut for CesFile {
-    fn compile_mut(&mut self, ctx: &ContextHandle) -> Result<bool, Box<dyn Error>> {
-        info!("Start compiling...");
+impl CesFile {
+    /// Compiles all definitions and context blocks of this file,
+    /// after compiling all imported files.
+    ///
+    /// A block failing to compile doesn't stop compilation of the
+    /// remaining blocks: its error is pushed to `errors`, unless it
+    /// isn't an [`AscesisError`], in which case it is returned
+    /// immediately.  Structures depending on a structure which failed
+    /// to compile are skipped, since their errors wouldn't be
+    /// independent.
+    fn compile_definitions(
+        &mut self,
+        ctx: &ContextHandle,
+        errors: &mut Vec<AscesisError>,
+    ) -> Result<(), Box<dyn Error>> {
+        for module in self.modules.iter_mut() {
+            module.compile_definitions(ctx, errors)?;
+        }
 
         // First pass: compile all property blocks.
 
         for block in self.blocks.iter().rev() {
             match block {
                 CesFileBlock::SAT(blk) | CesFileBlock::Vis(blk) => {
-                    blk.compile(ctx)?;
+                    if let Err(err) = blk.compile(ctx) {
+                        errors.push(into_ascesis_error(self.locate_error(err, blk.get_span()))?);
+                    }
                 }
                 _ => {}
             }
         }
 
-        // Second pass: compile all structural blocks having no dependencies.
+        // Second pass: compile all context blocks.
 
-        for block in self.blocks.iter_mut() {
+        for block in self.blocks.iter() {
             match block {
-                CesFileBlock::Imm(ref mut imm) => {
-                    imm.compile(ctx)?;
-                }
-                CesFileBlock::Caps(ref caps) => {
-                    caps.compile(ctx)?;
-                }
-                CesFileBlock::Unbounded(ref unbounded) => {
-                    unbounded.compile(ctx)?;
-                }
-                CesFileBlock::Weights(ref weights) => {
-                    weights.compile(ctx)?;
-                }
-                CesFileBlock::Inhibit(ref inhibit) => {
-                    inhibit.compile(ctx)?;
-                }
-                CesFileBlock::Activate(ref activate) => {
-                    activate.compile(ctx)?;
-                }
-                CesFileBlock::Drop(ref drop) => {
-                    drop.compile(ctx)?;
-                }
-                CesFileBlock::SAT(_) | CesFileBlock::Vis(_) => {}
-                CesFileBlock::Bad(err) => {
-                    println!("{:?}", err);
+                CesFileBlock::Caps(_)
+                | CesFileBlock::Unbounded(_)
+                | CesFileBlock::Weights(_)
+                | CesFileBlock::Inhibit(_)
+                | CesFileBlock::Activate(_)
+                | CesFileBlock::Drop(_) => {
+                    if let Some(span) = block.get_span() {
+                        if let Err(err) = block.compile(ctx) {
+                            errors.push(into_ascesis_error(self.locate_error(err, span))?);
+                        }
+                    }
                 }
+                // Bad blocks are reported by `get_warnings()`.
+                CesFileBlock::Use(_)
+                | CesFileBlock::Imm(_)
+                | CesFileBlock::Template(_)
+                | CesFileBlock::SAT(_)
+                | CesFileBlock::Vis(_)
+                | CesFileBlock::Bad(_) => {}
             }
         }
 
-        loop {
-            // Repeat compiling all resolvable uncompiled Imm blocks
-            // until reaching a fix point.
+        // Third pass: instantiate all templates in use.
 
-            let mut made_progress = false;
+        let failed_instances = self.instantiate_templates(ctx, errors);
 
-            for block in self.blocks.iter_mut() {
-                if let CesFileBlock::Imm(ref mut imm) = block {
-                    if !imm.is_compiled(ctx) && imm.compile(ctx)? {
-                        made_progress = true;
-                    }
-                }
+        for block in self.local_blocks.iter() {
+            if let Err(err) = block.compile(ctx) {
+                errors.push(into_ascesis_error(err)?);
             }
+        }
 
-            if !made_progress {
-                break
+        // Fourth pass: compile all structures, each after all of its
+        // dependencies.
+
+        let mut failed = BTreeSet::new();
+
+        for imm in self.get_compile_order(ctx, failed_instances, errors) {
+            if imm.rex.get_dependencies().iter().any(|(name, _)| failed.contains(name)) {
+                failed.insert(&imm.name);
+            } else if let Err(err) = imm.compile(ctx) {
+                errors.push(into_ascesis_error(self.locate_error(err, imm.span))?);
+                failed.insert(&imm.name);
             }
         }
 
+        Ok(())
+    }
+}
+
+impl CompilableMut for CesFile {
+    fn compile_mut(&mut self, ctx: &ContextHandle) -> Result<bool, Box<dyn Error>> {
+        info!("Start compiling...");
+
+        let mut stack: Vec<PathBuf> =
+            self.path.iter().filter_map(|p| fs::canonicalize(p).ok()).collect();
+
+        self.load_modules(&mut stack, &mut BTreeMap::new())?;
+
+        // Names are resolved in all definitions, before any of them is
+        // compiled, and independent errors of each stage are reported
+        // together.
+
+        let mut errors = Vec::new();
+
+        self.resolve_names(&mut errors);
+
+        if errors.is_empty() {
+            self.compile_definitions(ctx, &mut errors)?;
+        }
+
+        if !errors.is_empty() {
+            let err = AscesisError::from_errors(errors).with_default_script(self.script.as_deref());
+
+            return Err(err.into())
+        }
+
         let root = self.get_root()?;
 
         if root.is_compiled(ctx) {
@@ -279,12 +994,209 @@ impl CompilableMut for CesFile {
     }
 }
 
+/// Unboxes an [`AscesisError`], or returns `err` back, if it is of
+/// another type.
+fn into_ascesis_error(err: Box<dyn Error>) -> Result<AscesisError, Box<dyn Error>> {
+    err.downcast::<AscesisError>().map(|err| *err)
+}
+
+fn error_in_script(script: Option<&str>, kind: AscesisErrorKind) -> AscesisError {
+    if let Some(script) = script {
+        kind.with_script(script)
+    } else {
+        kind.into()
+    }
+}
+
+/// Returns the name most similar to `name` among `names`, if any is
+/// similar enough.  Unless `name` is qualified, only unqualified parts
+/// of `names` are compared, because an unqualified name may refer to
+/// an imported definition.
+fn find_similar_name<'a, I>(name: &CesName, names: I) -> Option<String>
+where
+    I: IntoIterator<Item = &'a CesName>,
+{
+    let names = names.into_iter();
+
+    if name.is_qualified() {
+        find_similar(name.as_str(), names.map(|name| name.as_str()))
+    } else {
+        find_similar(name.as_str(), names.map(|name| name.get_unqualified()))
+    }
+    .map(ToOwned::to_owned)
+}
+
+pub(crate) fn join_doc_lines(lines: Vec<String>) -> Option<String> {
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
+}
+
+/// Writes documentation as a sequence of doc comment lines, each
+/// terminated with a newline.
+pub(crate) fn fmt_doc_lines(doc: Option<&str>, f: &mut fmt::Formatter) -> fmt::Result {
+    if let Some(doc) = doc {
+        for line in doc.split('\n') {
+            if line.is_empty() {
+                writeln!(f, "///")?;
+            } else {
+                writeln!(f, "/// {}", line)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Names of structures, or of templates, visible in a file.
+#[derive(Default, Debug)]
+struct NameTable {
+    /// Maps names of definitions in the file to their qualified names.
+    local:    BTreeMap<CesName, CesName>,
+    /// Maps unqualified names of imported definitions to all of their
+    /// qualified names.
+    imported: BTreeMap<String, BTreeSet<CesName>>,
+}
+
+impl NameTable {
+    fn insert_local(&mut self, name: &CesName, namespace: Option<&str>) {
+        let qualified = match namespace {
+            Some(namespace) if !name.is_qualified() => name.with_namespace(namespace),
+            _ => name.clone(),
+        };
+
+        self.local.insert(name.clone(), qualified);
+    }
+
+    fn insert_imported(&mut self, qualified: CesName) {
+        self.imported.entry(qualified.get_unqualified().to_owned()).or_default().insert(qualified);
+    }
+
+    /// Returns the qualified name referred to by `name`, if it differs
+    /// from `name`.
+    fn resolve(&self, name: &CesName, span: Span) -> Result<Option<CesName>, AscesisErrorKind> {
+        if name.is_qualified() {
+            Ok(None)
+        } else if let Some(qualified) = self.local.get(name) {
+            Ok(Some(qualified.clone()).filter(|qualified| qualified != name))
+        } else if let Some(candidates) = self.imported.get(name.as_str()) {
+            if candidates.len() == 1 {
+                Ok(candidates.iter().next().cloned())
+            } else {
+                Err(AscesisErrorKind::StructureAmbiguous(
+                    name.to_string(),
+                    candidates.iter().map(|c| c.to_string()).collect(),
+                    span,
+                ))
+            }
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Depth-first traversal of the graph of dependencies between
+/// structure definitions.
+#[derive(Default)]
+struct DependencySorter<'a> {
+    defs:             BTreeMap<&'a CesName, &'a ImmediateDef>,
+    failed_instances: BTreeSet<CesName>,
+    done:             BTreeSet<&'a CesName>,
+    failed:           BTreeSet<&'a CesName>,
+    path:             Vec<&'a CesName>,
+    order:            Vec<&'a ImmediateDef>,
+    errors:           Vec<AscesisErrorKind>,
+}
+
+impl<'a> DependencySorter<'a> {
+    /// Visits `imm` and, first, all definitions it depends on.
+    ///
+    /// A definition on a dependency cycle, or depending on an undefined
+    /// structure, fails, and so do all definitions depending on it.
+    /// An error is recorded for each cycle and each undefined
+    /// structure, but not for failing dependencies.
+    fn visit(&mut self, imm: &'a ImmediateDef, ctx: &ContextHandle) {
+        if self.done.contains(&imm.name) || self.failed.contains(&imm.name) {
+            return
+        }
+
+        self.path.push(&imm.name);
+
+        for (dep_name, span) in imm.rex.get_dependencies() {
+            if let Some(pos) = self.path.iter().position(|name| **name == dep_name) {
+                let mut cycle: Vec<String> =
+                    self.path[pos..].iter().map(|name| name.to_string()).collect();
+
+                cycle.push(dep_name.to_string());
+
+                let def_span = self.defs.get(&dep_name).map(|def| def.span).unwrap_or_default();
+
+                self.errors.push(AscesisErrorKind::StructureCycle(cycle, span, def_span));
+                self.failed.extend(self.path[pos..].iter().copied());
+            } else if let Some(&dep) = self.defs.get(&dep_name) {
+                self.visit(dep, ctx);
+
+                if self.failed.contains(&dep_name) {
+                    self.failed.insert(&imm.name);
+                }
+            } else if self.failed_instances.contains(&dep_name) {
+                self.failed.insert(&imm.name);
+            } else if !ctx.lock().unwrap().has_content(&dep_name) {
+                let similar = find_similar_name(&dep_name, self.defs.keys().copied());
+
+                self.errors.push(AscesisErrorKind::StructureUndefined(
+                    dep_name.to_string(),
+                    similar,
+                    span,
+                ));
+                self.failed.insert(&imm.name);
+            }
+        }
+
+        self.path.pop();
+
+        if !self.failed.contains(&imm.name) {
+            self.done.insert(&imm.name);
+            self.order.push(imm);
+        }
+    }
+}
+
 impl From<Vec<CesFileBlock>> for CesFile {
     fn from(blocks: Vec<CesFileBlock>) -> Self {
         CesFile { blocks, ..Default::default() }
     }
 }
 
+/// Prints a `CesFile` as _Ascesis_ source, one block after another,
+/// separated with empty lines.
+///
+/// Only the blocks parsed from the script are printed, i.e. imported
+/// modules and structures instantiated from templates are not.
+impl fmt::Display for CesFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut is_first = true;
+
+        for block in self.blocks.iter() {
+            if let CesFileBlock::Bad(_) = block {
+                continue
+            }
+
+            if is_first {
+                is_first = false;
+            } else {
+                writeln!(f)?;
+            }
+
+            writeln!(f, "{}", block)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl Content for CesFile {
     #[inline]
     fn get_script(&self) -> Option<&str> {
@@ -320,9 +1232,11 @@ impl Content for CesFile {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum CesFileBlock {
+    Use(UseDirective),
     Imm(ImmediateDef),
+    Template(TemplateDef),
     Vis(PropBlock),
     SAT(PropBlock),
     Caps(CapacitiesBlock),
@@ -334,6 +1248,129 @@ pub enum CesFileBlock {
     Bad(AscesisError),
 }
 
+impl CesFileBlock {
+    /// Renames dots in a block defining a structure or providing
+    /// context.  Blocks of other kinds are left unchanged.  Returns a
+    /// warning for each capacity or label dropped after renaming.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) -> Vec<AscesisWarning> {
+        match self {
+            CesFileBlock::Vis(blk) => return blk.rename_dots(dot_map),
+            CesFileBlock::Caps(caps) => return caps.rename_dots(dot_map),
+            CesFileBlock::Template(tmp) => return tmp.rename_dots(dot_map),
+            CesFileBlock::Unbounded(unbounded) => unbounded.rename_dots(dot_map),
+            CesFileBlock::Weights(weights) => return weights.rename_dots(dot_map),
+            CesFileBlock::Inhibit(inhibit) => inhibit.rename_dots(dot_map),
+            CesFileBlock::Activate(activate) => activate.rename_dots(dot_map),
+            CesFileBlock::Drop(drop) => drop.rename_dots(dot_map),
+            CesFileBlock::Imm(imm) => imm.rex.rename_dots(dot_map),
+            CesFileBlock::Use(_) | CesFileBlock::SAT(_) | CesFileBlock::Bad(_) => {}
+        }
+
+        Vec::new()
+    }
+
+    pub(crate) fn bind_literals(
+        &mut self,
+        literals: &BTreeMap<String, Literal>,
+    ) -> Result<(), AscesisError> {
+        match self {
+            CesFileBlock::Vis(blk) | CesFileBlock::SAT(blk) => blk.bind_literals(literals),
+            CesFileBlock::Caps(caps) => caps.bind_literals(literals)?,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Returns names of all dots referenced in this block, if it is a
+    /// context block, in alphabetical order.  Labeled dots are
+    /// included for a `vis` block.
+    pub(crate) fn get_dot_names(&self) -> BTreeSet<&str> {
+        let dot_names = match self {
+            CesFileBlock::Vis(blk) => return blk.get_labeled_dot_names().into_iter().collect(),
+            CesFileBlock::Caps(caps) => caps.get_dot_names(),
+            CesFileBlock::Unbounded(unbounded) => unbounded.get_dot_names(),
+            CesFileBlock::Weights(weights) => weights.get_dot_names(),
+            CesFileBlock::Inhibit(inhibit) => inhibit.get_dot_names(),
+            CesFileBlock::Activate(activate) => activate.get_dot_names(),
+            CesFileBlock::Drop(drop) => drop.get_dot_names(),
+            CesFileBlock::Use(_)
+            | CesFileBlock::Imm(_)
+            | CesFileBlock::Template(_)
+            | CesFileBlock::SAT(_)
+            | CesFileBlock::Bad(_) => Vec::new(),
+        };
+
+        dot_names.into_iter().map(|dot_name| dot_name.as_ref()).collect()
+    }
+
+    /// Returns the span of this block, unless the block is [`Bad`].
+    ///
+    /// [`Bad`]: CesFileBlock::Bad
+    pub fn get_span(&self) -> Option<Span> {
+        match self {
+            CesFileBlock::Use(directive) => Some(*directive.span),
+            CesFileBlock::Imm(imm) => Some(imm.span),
+            CesFileBlock::Template(template) => Some(template.get_span()),
+            CesFileBlock::Vis(blk) | CesFileBlock::SAT(blk) => Some(blk.get_span()),
+            CesFileBlock::Caps(caps) => Some(caps.get_span()),
+            CesFileBlock::Unbounded(unbounded) => Some(unbounded.get_span()),
+            CesFileBlock::Weights(weights) => Some(weights.get_span()),
+            CesFileBlock::Inhibit(inhibit) => Some(inhibit.get_span()),
+            CesFileBlock::Activate(activate) => Some(activate.get_span()),
+            CesFileBlock::Drop(drop) => Some(drop.get_span()),
+            CesFileBlock::Bad(_) => None,
+        }
+    }
+}
+
+/// Prints a `CesFileBlock` as _Ascesis_ source.  A [`Bad`] block
+/// prints nothing.
+///
+/// [`Bad`]: CesFileBlock::Bad
+impl fmt::Display for CesFileBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CesFileBlock::Use(directive) => directive.fmt(f),
+            CesFileBlock::Imm(imm) => imm.fmt(f),
+            CesFileBlock::Template(template) => template.fmt(f),
+            CesFileBlock::Vis(blk) | CesFileBlock::SAT(blk) => blk.fmt(f),
+            CesFileBlock::Caps(caps) => caps.fmt(f),
+            CesFileBlock::Unbounded(unbounded) => unbounded.fmt(f),
+            CesFileBlock::Weights(weights) => weights.fmt(f),
+            CesFileBlock::Inhibit(inhibit) => inhibit.fmt(f),
+            CesFileBlock::Activate(activate) => activate.fmt(f),
+            CesFileBlock::Drop(drop) => drop.fmt(f),
+            CesFileBlock::Bad(_) => Ok(()),
+        }
+    }
+}
+
+impl Compilable for CesFileBlock {
+    /// Compiles a context block; this is a no-op for structure
+    /// definitions.
+    fn compile(&self, ctx: &ContextHandle) -> Result<bool, Box<dyn Error>> {
+        match self {
+            CesFileBlock::SAT(blk) | CesFileBlock::Vis(blk) => blk.compile(ctx),
+            CesFileBlock::Caps(caps) => caps.compile(ctx),
+            CesFileBlock::Unbounded(unbounded) => unbounded.compile(ctx),
+            CesFileBlock::Weights(weights) => weights.compile(ctx),
+            CesFileBlock::Inhibit(inhibit) => inhibit.compile(ctx),
+            CesFileBlock::Activate(activate) => activate.compile(ctx),
+            CesFileBlock::Drop(drop) => drop.compile(ctx),
+            CesFileBlock::Use(_) | CesFileBlock::Imm(_) | CesFileBlock::Template(_) => Ok(false),
+            CesFileBlock::Bad(err) => Err(err.clone().into()),
+        }
+    }
+}
+
+impl From<UseDirective> for CesFileBlock {
+    #[inline]
+    fn from(directive: UseDirective) -> Self {
+        CesFileBlock::Use(directive)
+    }
+}
+
 impl From<ImmediateDef> for CesFileBlock {
     #[inline]
     fn from(imm: ImmediateDef) -> Self {
@@ -341,15 +1378,23 @@ impl From<ImmediateDef> for CesFileBlock {
     }
 }
 
+impl From<TemplateDef> for CesFileBlock {
+    #[inline]
+    fn from(template: TemplateDef) -> Self {
+        CesFileBlock::Template(template)
+    }
+}
+
 impl From<PropBlock> for CesFileBlock {
     fn from(props: PropBlock) -> Self {
         match props.get_selector() {
-            Ok(PropSelector::AnonymousBlock) => {
-                CesFileBlock::Bad(AscesisErrorKind::MissingPropSelector.into())
-            }
+            Ok(PropSelector::AnonymousBlock) => CesFileBlock::Bad(
+                AscesisError::from(AscesisErrorKind::MissingPropSelector)
+                    .with_span(props.get_span()),
+            ),
             Ok(PropSelector::Vis) => CesFileBlock::Vis(props),
             Ok(PropSelector::SAT) => CesFileBlock::SAT(props),
-            Err(err) => CesFileBlock::Bad(err),
+            Err(err) => CesFileBlock::Bad(err.with_span(props.get_span())),
             _ => unreachable!(),
         }
     }
@@ -394,6 +1439,39 @@ impl From<WeightlessBlock> for CesFileBlock {
     }
 }
 
+/// A `use` directive, importing all definitions of another file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UseDirective {
+    pub(crate) path: String,
+    pub(crate) span: NodeSpan,
+}
+
+impl UseDirective {
+    pub(crate) fn new(path: Literal) -> Result<Self, AscesisError> {
+        if let Literal::Name(path) = path {
+            Ok(UseDirective { path, span: Default::default() })
+        } else {
+            Err(AscesisErrorKind::ExpectedNameLiteral.into())
+        }
+    }
+
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span.into();
+        self
+    }
+
+    #[inline]
+    pub fn get_path(&self) -> &str {
+        self.path.as_str()
+    }
+}
+
+impl fmt::Display for UseDirective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "use {}", Literal::Name(self.path.clone()))
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
 pub struct CesName(String);
 
@@ -420,6 +1498,29 @@ impl AsRef<str> for CesName {
     }
 }
 
+impl CesName {
+    /// Returns `true` if this name is qualified with a namespace, as in
+    /// `arrows::Fork`.
+    #[inline]
+    pub fn is_qualified(&self) -> bool {
+        self.0.contains("::")
+    }
+
+    /// Returns the namespace part of a qualified name.
+    pub fn get_namespace(&self) -> Option<&str> {
+        self.0.rsplitn(2, "::").nth(1)
+    }
+
+    /// Returns this name without its namespace part.
+    pub fn get_unqualified(&self) -> &str {
+        self.0.rsplit("::").next().unwrap_or_default()
+    }
+
+    pub(crate) fn with_namespace(&self, namespace: &str) -> Self {
+        CesName(format!("{}::{}", namespace, self.get_unqualified()))
+    }
+}
+
 impl fmt::Display for CesName {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -440,14 +1541,50 @@ impl<S: AsRef<str>> ToCesName for S {
 
 #[derive(Clone, Debug)]
 pub struct ImmediateDef {
-    name: CesName,
-    rex:  Rex,
+    pub(crate) name: CesName,
+    pub(crate) rex:  Rex,
+    doc:             Option<String>,
+    span:            Span,
 }
 
 impl ImmediateDef {
     pub fn new(name: CesName, rex: Rex) -> Self {
         debug!("ImmediateDef of '{}': {:?}", name, rex);
-        ImmediateDef { name, rex }
+        ImmediateDef { name, rex, doc: None, span: Span::default() }
+    }
+
+    #[inline]
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+
+    /// Attaches documentation given as a sequence of doc comment
+    /// lines, which are joined with newlines.  Empty sequence leaves
+    /// the definition undocumented.
+    pub fn with_doc(mut self, lines: Vec<String>) -> Self {
+        self.doc = join_doc_lines(lines);
+        self
+    }
+
+    #[inline]
+    pub fn get_name(&self) -> &CesName {
+        &self.name
+    }
+
+    #[inline]
+    pub fn get_rex(&self) -> &Rex {
+        &self.rex
+    }
+
+    #[inline]
+    pub fn get_doc(&self) -> Option<&str> {
+        self.doc.as_deref()
     }
 
     pub(crate) fn is_compiled(&self, ctx: &ContextHandle) -> bool {
@@ -455,6 +1592,20 @@ impl ImmediateDef {
     }
 }
 
+/// Prints an `ImmediateDef` as _Ascesis_ source, preceded by its doc
+/// comments, if any.
+impl fmt::Display for ImmediateDef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_doc_lines(self.get_doc(), f)?;
+
+        if self.rex.kinds.is_empty() {
+            write!(f, "ces {} {{}}", self.name)
+        } else {
+            write!(f, "ces {} {{ {} }}", self.name, self.rex)
+        }
+    }
+}
+
 impl Compilable for ImmediateDef {
     fn compile(&self, ctx: &ContextHandle) -> Result<bool, Box<dyn Error>> {
         if log_enabled!(Debug) {
@@ -511,28 +1662,460 @@ impl CompilableAsDependency for ImmediateDef {
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct CesImmediate {
     pub(crate) name: CesName,
+    pub(crate) span: NodeSpan,
 }
 
 impl CesImmediate {
     pub(crate) fn new(name: CesName) -> Self {
-        CesImmediate { name }
+        CesImmediate { name, span: Default::default() }
+    }
+
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span.into();
+        self
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        *self.span
+    }
+
+    pub(crate) fn rename_structures(&mut self, ces_map: &BTreeMap<CesName, CesName>) {
+        if let Some(new_name) = ces_map.get(&self.name) {
+            self.name = new_name.clone();
+        }
+    }
+}
+
+impl fmt::Display for CesImmediate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}()", self.name)
     }
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct CesInstance {
     pub(crate) name: CesName,
-    pub(crate) args: Vec<String>,
+    pub(crate) args: Vec<ArgValue>,
+    pub(crate) span: NodeSpan,
 }
 
 impl CesInstance {
     pub(crate) fn new(name: CesName) -> Self {
         debug!("CesInstance of '{}'", name);
-        CesInstance { name, args: Vec::new() }
+        CesInstance { name, args: Vec::new(), span: Default::default() }
+    }
+
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span.into();
+        self
     }
 
-    pub(crate) fn with_args(mut self, mut args: Vec<String>) -> Self {
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        *self.span
+    }
+
+    pub(crate) fn with_args(mut self, mut args: Vec<ArgValue>) -> Self {
         self.args.append(&mut args);
         self
     }
+
+    /// Returns the name under which the result of this instantiation
+    /// is stored in the context.
+    #[inline]
+    pub(crate) fn get_instance_name(&self) -> CesName {
+        self.to_string().into()
+    }
+
+    pub(crate) fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) {
+        for arg in self.args.iter_mut() {
+            if let ArgValue::Identifier(dot_name) = arg {
+                if let Some(new_name) = dot_map.get(&dot_name.to_dot()) {
+                    *dot_name = new_name.as_ref().to_owned();
+                }
+            }
+        }
+    }
+
+    /// Replaces arguments naming a parameter of the enclosing template
+    /// with the value bound to that parameter in `args`.
+    pub(crate) fn substitute_args(&mut self, args: &BTreeMap<String, ArgValue>) {
+        for arg in self.args.iter_mut() {
+            if let ArgValue::Identifier(param) = arg {
+                if let Some(value) = args.get(param) {
+                    *arg = value.clone();
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for CesInstance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let args: Vec<_> = self.args.iter().map(|arg| arg.to_string()).collect();
+
+        write!(f, "{}!({})", self.name, args.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aces::Context;
+    use super::*;
+
+    /// A temporary directory of scripts, removed when dropped.
+    struct TempFiles(PathBuf);
+
+    impl std::ops::Deref for TempFiles {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempFiles {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Writes `files` into a fresh directory named after `name`, under
+    /// the system temporary directory.
+    fn write_files(name: &str, files: &[(&str, &str)]) -> TempFiles {
+        let dir = TempFiles(std::env::temp_dir().join(format!(
+            "ascesis-{}-{}",
+            name,
+            std::process::id()
+        )));
+
+        for (path, script) in files {
+            let path = dir.join(path);
+
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, script).unwrap();
+        }
+
+        dir
+    }
+
+    fn compile_main(path: &Path, ctx: &ContextHandle) -> Result<CesFile, Box<dyn Error>> {
+        let mut ces_file = CesFile::from_path(path)?;
+
+        ces_file.set_root_name("Main")?;
+        ces_file.compile_mut(ctx)?;
+
+        Ok(ces_file)
+    }
+
+    fn get_error_code(path: &Path) -> &'static str {
+        let ctx = Context::new_toplevel("Main");
+        let err = compile_main(path, &ctx).unwrap_err().downcast::<AscesisError>().unwrap();
+
+        err.get_kind().get_code()
+    }
+
+    #[test]
+    fn test_independent_errors() {
+        let script = "ces Main { A() + B() }
+                      ces A { { a -> b } + Missing1() }
+                      ces B { { c -> d } + Missing2() }
+                      ces C { D() }
+                      ces D { C() }";
+        let mut ces_file = CesFile::from_script(script).unwrap();
+        let ctx = Context::new_toplevel("Main");
+
+        ces_file.set_root_name("Main").unwrap();
+
+        let err = ces_file.compile_mut(&ctx).unwrap_err().downcast::<AscesisError>().unwrap();
+        let codes: Vec<_> =
+            err.to_diagnostics().iter().map(|diagnostic| diagnostic.get_code().unwrap()).collect();
+
+        assert_eq!(codes, vec!["E0210", "E0210", "E0211"]);
+    }
+
+    #[test]
+    fn test_similar_names() {
+        let script = "ces Main { { a -> b } + Sourc() }
+                      ces Source { src -> a }
+                      caps { 2 a, 3 bb }
+                      vis { lables: { a: \"A\" } }";
+        let mut ces_file = CesFile::from_script(script).unwrap();
+        let warnings: Vec<_> =
+            ces_file.get_warnings().into_iter().map(|w| w.get_kind().clone()).collect();
+
+        assert_eq!(
+            warnings,
+            vec![
+                AscesisWarningKind::DotUnused("bb".into(), Some("b".into())),
+                AscesisWarningKind::PropKeyUnknown(
+                    PropSelector::Vis,
+                    "lables".into(),
+                    Some("labels".into())
+                ),
+            ]
+        );
+
+        let ctx = Context::new_toplevel("Main");
+
+        ces_file.set_root_name("Main").unwrap();
+
+        let err = ces_file.compile_mut(&ctx).unwrap_err().downcast::<AscesisError>().unwrap();
+
+        assert!(matches!(
+            err.get_kind(),
+            AscesisErrorKind::StructureUndefined(_, Some(similar), _) if similar == "Source"
+        ));
+    }
+
+    #[test]
+    fn test_recovery() {
+        let script = "ces A { a -> -> b }
+                      ces B { { c -> d } + { e => } + A() }
+                      caps { 2 a, 3 +, 1 b }
+                      vis { title: \"x\", bad, labels: { a: \"A\" } }
+                      ces T(x: Dot) { x => => b }
+                      ces Main { B() + T!(a) }";
+        let (ces_file, err) = CesFile::from_script_recovering(script).unwrap();
+        let blocks = ces_file.get_blocks();
+
+        assert_eq!(err.unwrap().to_diagnostics().len(), 5);
+        assert_eq!(blocks.len(), 6);
+        assert!(matches!(blocks[4], CesFileBlock::Template(_)));
+        assert!(blocks.iter().all(|block| block.get_span().is_some()));
+        assert_eq!(blocks[2].to_string(), "caps { 2 a, 1 b }");
+
+        // Phrases are parsed without recovery.
+        assert!("{ c -> d } + { e => }".parse::<Rex>().is_err());
+    }
+
+    #[test]
+    fn test_imports() {
+        let dir = write_files(
+            "imports",
+            &[
+                ("lib/base.ces", "ces Base { p => q }"),
+                (
+                    "lib/arrows.ces",
+                    "use \"base.ces\"
+                     ces Arrow(x: Dot, y: Dot) { x => y }
+                     ces Pair { Base() + Arrow!(q, r) }",
+                ),
+                ("main.ces", "use \"lib/arrows.ces\" ces Main { Arrow!(a, z) + Pair() }"),
+                ("hidden.ces", "use \"lib/arrows.ces\" ces Main { Base() }"),
+            ],
+        );
+        let ctx = Context::new_toplevel("Main");
+        let ces_file = compile_main(&dir.join("main.ces"), &ctx).unwrap();
+
+        assert_eq!(ces_file.get_modules()[0].get_namespace(), Some("arrows"));
+
+        // Definitions imported by an imported file aren't visible.
+        assert_eq!(get_error_code(&dir.join("hidden.ces")), "E0210");
+
+        let dir = write_files(
+            "import-errors",
+            &[
+                ("missing.ces", "use \"lib/missing.ces\" ces Main { a => b }"),
+                ("cycle.ces", "use \"lib/cycle.ces\" ces Main { a => b }"),
+                ("lib/cycle.ces", "use \"../cycle.ces\""),
+                ("conflict.ces", "use \"a/util.ces\" use \"b/util.ces\" ces Main { a => b }"),
+                ("a/util.ces", "ces A { a => b }"),
+                ("b/util.ces", "ces B { b => c }"),
+            ],
+        );
+
+        assert_eq!(get_error_code(&dir.join("missing.ces")), "E0213");
+        assert_eq!(get_error_code(&dir.join("cycle.ces")), "E0214");
+        assert_eq!(get_error_code(&dir.join("conflict.ces")), "E0217");
+    }
+
+    #[test]
+    fn test_name_resolution() {
+        let dir = write_files(
+            "resolution",
+            &[
+                ("lib/a.ces", "ces Loop { a => a } ces Lone { x => y }"),
+                ("lib/b.ces", "ces Loop { b => b }"),
+                (
+                    "main.ces",
+                    "use \"lib/a.ces\" use \"lib/b.ces\"
+                     ces Mark(x: Dot) { x => done }
+                     ces Main { a::Loop() + b::Loop() + Lone() + Mark!(Lone) }",
+                ),
+                ("ambiguous.ces", "use \"lib/a.ces\" use \"lib/b.ces\" ces Main { Loop() }"),
+            ],
+        );
+        let ctx = Context::new_toplevel("Main");
+        let ces_file = compile_main(&dir.join("main.ces"), &ctx).unwrap();
+        let main = ces_file.get_blocks().iter().find_map(|block| match block {
+            CesFileBlock::Imm(imm) if imm.name.as_str() == "Main" => Some(imm),
+            _ => None,
+        });
+
+        // A dot argument isn't taken for the imported structure.
+        assert_eq!(
+            main.unwrap().rex.to_string(),
+            "a::Loop() + b::Loop() + a::Lone() + Mark!(Lone)"
+        );
+
+        assert_eq!(get_error_code(&dir.join("ambiguous.ces")), "E0212");
+    }
+
+    #[test]
+    fn test_hybrid_import() {
+        let dir = write_files(
+            "hybrid",
+            &[
+                ("lib/loops.ces", "ces Loop { a => a }"),
+                (
+                    "main.ces",
+                    "use \"lib/loops.ces\"
+                     ces Grow(x: Hybrid) { x() { b => c } }
+                     ces Main { Grow!(Loop) }",
+                ),
+            ],
+        );
+        let ctx = Context::new_toplevel("Main");
+        let ces_file = compile_main(&dir.join("main.ces"), &ctx).unwrap();
+
+        assert_eq!(ces_file.instances[0].rex.to_string(), "loops::Loop() { b => c }");
+    }
+
+    #[test]
+    fn test_rename_dots() {
+        let script = "ces Fork(x: Dot) { x => a c }
+                      ces Main { { a -> b } + { c -> b } + Fork!(a) }
+                      caps { 2 a, 3 c }
+                      vis { labels: { a: \"A\", c: \"C\" } }";
+        let mut ces_file = CesFile::from_script(script).unwrap();
+        let dot_map =
+            vec![("a".to_dot(), "c".to_dot()), ("x".to_dot(), "y".to_dot())].into_iter().collect();
+
+        let warnings: Vec<_> =
+            ces_file.rename_dots(&dot_map).iter().map(|w| w.get_kind().to_string()).collect();
+        let blocks: Vec<_> = ces_file.get_blocks().iter().map(|block| block.to_string()).collect();
+
+        assert_eq!(
+            blocks,
+            vec![
+                "ces Fork(x: Dot) { x => c }",
+                "ces Main { { c -> b } + { c -> b } + Fork!(c) }",
+                "caps { 3 c }",
+                "vis { labels: { c: \"C\" } }",
+            ]
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                "Dots 'a' and 'c' are both renamed to 'c', dropping the capacity of 'a'",
+                "Dots 'a' and 'c' are both renamed to 'c', dropping the label of 'a'",
+            ]
+        );
+
+        // Equal values are merged silently.
+        let mut caps: CapacitiesBlock = "caps { 2 a c }".parse().unwrap();
+
+        assert!(caps.rename_dots(&dot_map).is_empty());
+        assert_eq!(caps.to_string(), "caps { 2 c }");
+
+        // A constant capacity takes precedence over a size parameter.
+        let mut template: TemplateDef =
+            "ces T(n: Size) { a => c caps { 2 a, n c } }".parse().unwrap();
+        let warnings = template.rename_dots(&dot_map);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(template.get_blocks()[0].to_string(), "caps { 2 c }");
+
+        // Transfers merged by renaming keep a single weight.
+        let script = "ces Main { a b => c }
+                      weights { 2 a -> c, 3 c -> c, 4 b <- a c, 4 b <- c, 7 b -> a, 8 b -> c }";
+        let mut ces_file = CesFile::from_script(script).unwrap();
+        let warnings: Vec<_> =
+            ces_file.rename_dots(&dot_map).iter().map(|w| w.get_kind().to_string()).collect();
+
+        assert_eq!(
+            ces_file.get_blocks()[1].to_string(),
+            "weights { 4 b <- c, 8 b -> c, 3 c -> c }"
+        );
+        assert_eq!(
+            warnings,
+            vec!["Dots 'a' and 'c' are both renamed to 'c', dropping the weight of 'a'"; 2]
+        );
+    }
+
+    #[test]
+    fn test_doc_comments() {
+        let script = r#"
+/// An arrow from `x`
+///   to `y`.
+ces Arrow(x: Dot, y: Dot) {
+    x => y
+
+    /// Local capacities.
+    caps { 2 x }
+}
+
+/// Labels.
+vis { labels: { a: "Source", z: "Sink" } }
+
+ces Main { Arrow!(a, z) }
+"#;
+        let ces_file = CesFile::from_script(script).unwrap();
+
+        assert_eq!(
+            ces_file.get_docs(),
+            vec![
+                (&"Arrow".to_ces_name(), Some("An arrow from `x`\n  to `y`.")),
+                (&"Main".to_ces_name(), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_display() {
+        let script = r#"
+use "lib/arrows.ces"
+/// An arrow from `x`
+///   to `y`.
+ces Arrow(x: Dot, y: Dot, cap: Size) { x => y caps { cap x, 2 y } }
+vis { title: "Main", labels: { a: "Source", z: "Sink" } }
+caps { 3 a z, ω b }
+weights { 2 a -> z, 2 b -> z }
+inhibit { a <- b c }
+drop { z <- a + b }
+ces Main { Arrow!(a, z, 2) + Empty() }
+ces Empty {}
+"#;
+        let printed = CesFile::from_script(script).unwrap().to_string();
+
+        assert_eq!(
+            printed,
+            r#"use "lib/arrows.ces"
+
+/// An arrow from `x`
+///   to `y`.
+ces Arrow(x: Dot, y: Dot, cap: Size) {
+    x => y
+    caps { 2 y, cap x }
+}
+
+vis { labels: { a: "Source", z: "Sink" }, title: "Main" }
+
+caps { 3 a z, ω b }
+
+weights { 2 a -> z, 2 b -> z }
+
+inhibit { a <- b c }
+
+drop { z <- a, z <- b }
+
+ces Main { Arrow!(a, z, 2) + Empty() }
+
+ces Empty {}
+"#
+        );
+        assert_eq!(CesFile::from_script(&printed).unwrap().to_string(), printed);
+    }
 }
@@ -0,0 +1,174 @@
+//! Detection of links specified on one side only.
+//!
+//! A compiled structure is coherent if every link between two dots
+//! is specified on both sides, i.e. if, whenever dot `a` has an
+//! effect monomial containing dot `b`, then `b` has a cause monomial
+//! containing `a`, and vice versa.  A thin arrow rule usually
+//! specifies one side of its links only, and unless another rule
+//! specifies the other side, the structure isn't coherent.
+
+use std::fmt;
+use aces::Polarity;
+use crate::{
+    CesName, ThinArrowRule, DotName, AscesisWarning, AscesisWarningKind,
+    equivalence::DotPolynomials,
+};
+
+/// A link specified on one side only: an effect-only link `(a >? b)`
+/// of [`Polarity::Tx`], or a cause-only link `(a ?> b)` of
+/// [`Polarity::Rx`], where `a` is the sending dot and `b` the
+/// receiving one.
+///
+/// A link found in a [`CesFile`](crate::CesFile) points back to the
+/// thin arrow rule which specifies it, and to the structure defining
+/// that rule, see
+/// [`CesFile::get_incoherent_links()`](crate::CesFile::get_incoherent_links).
+#[derive(Clone, Debug)]
+pub struct IncoherentLink {
+    tx_dot:   DotName,
+    rx_dot:   DotName,
+    polarity: Polarity,
+    origin:   Option<(CesName, ThinArrowRule)>,
+}
+
+impl IncoherentLink {
+    #[inline]
+    pub fn get_tx_dot(&self) -> &DotName {
+        &self.tx_dot
+    }
+
+    #[inline]
+    pub fn get_rx_dot(&self) -> &DotName {
+        &self.rx_dot
+    }
+
+    /// Returns [`Polarity::Tx`] for an effect-only link, and
+    /// [`Polarity::Rx`] for a cause-only link.
+    #[inline]
+    pub fn get_polarity(&self) -> Polarity {
+        self.polarity
+    }
+
+    /// Returns the name of the structure and the thin arrow rule which
+    /// specify this link, if found.
+    pub fn get_origin(&self) -> Option<(&CesName, &ThinArrowRule)> {
+        self.origin.as_ref().map(|(name, rule)| (name, rule))
+    }
+
+    pub(crate) fn with_origin(mut self, name: CesName, rule: ThinArrowRule) -> Self {
+        self.origin = Some((name, rule));
+        self
+    }
+
+    /// Returns `true` if `rule` specifies the side of this link which
+    /// is specified.
+    pub(crate) fn is_specified_by(&self, rule: &ThinArrowRule) -> bool {
+        rule.specifies_link(&self.tx_dot, &self.rx_dot, self.polarity)
+    }
+
+    /// Returns a warning spanning the rule this link originates from.
+    pub fn to_warning(&self) -> AscesisWarning {
+        let warning = AscesisWarning::from(AscesisWarningKind::LinkIncoherent(
+            self.tx_dot.clone(),
+            self.rx_dot.clone(),
+            self.polarity,
+        ));
+
+        if let Some((_, ref rule)) = self.origin {
+            warning.with_default_span(rule.get_span())
+        } else {
+            warning
+        }
+    }
+}
+
+/// Prints an `IncoherentLink` in the notation of the parser
+/// implementation notes, i.e. `(a >? b)` or `(a ?> b)`.
+impl fmt::Display for IncoherentLink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.polarity {
+            Polarity::Tx => write!(f, "({} >? {})", self.tx_dot, self.rx_dot),
+            Polarity::Rx => write!(f, "({} ?> {})", self.tx_dot, self.rx_dot),
+        }
+    }
+}
+
+/// Returns all links of a compiled structure which are specified on
+/// one side only, effect-only links first, each group ordered by
+/// sending dot, then by receiving dot.
+pub(crate) fn find_incoherent_links(dot_polys: &DotPolynomials) -> Vec<IncoherentLink> {
+    let mut links = Vec::new();
+    let has_side =
+        |dot_name: &DotName, other: &DotName, polarity: Polarity| match dot_polys.get(dot_name) {
+            Some((_, effects)) if polarity == Polarity::Tx => effects.contains_dot(other),
+            Some((causes, _)) => causes.contains_dot(other),
+            None => false,
+        };
+
+    for polarity in [Polarity::Tx, Polarity::Rx].iter().copied() {
+        let mut pairs = Vec::new();
+
+        for (dot_name, (causes, effects)) in dot_polys.iter() {
+            if polarity == Polarity::Tx {
+                for rx_dot in effects.get_dot_names() {
+                    if !has_side(rx_dot, dot_name, Polarity::Rx) {
+                        pairs.push((dot_name.clone(), rx_dot.clone()));
+                    }
+                }
+            } else {
+                for tx_dot in causes.get_dot_names() {
+                    if !has_side(tx_dot, dot_name, Polarity::Tx) {
+                        pairs.push((tx_dot.clone(), dot_name.clone()));
+                    }
+                }
+            }
+        }
+
+        pairs.sort();
+        pairs.dedup();
+
+        links.extend(pairs.into_iter().map(|(tx_dot, rx_dot)| IncoherentLink {
+            tx_dot,
+            rx_dot,
+            polarity,
+            origin: None,
+        }));
+    }
+
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use aces::{Context, CompilableMut};
+    use crate::CesFile;
+
+    fn get_links(script: &str) -> Vec<(String, Option<String>)> {
+        let ctx = Context::new_toplevel("coherence");
+        let mut ces_file = CesFile::from_script(script).unwrap();
+
+        ces_file.set_root_name("Main").unwrap();
+        ces_file.compile_mut(&ctx).unwrap();
+
+        ces_file
+            .get_incoherent_links(&ctx)
+            .unwrap()
+            .iter()
+            .map(|link| (link.to_string(), link.get_origin().map(|(_, rule)| rule.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn test_coherence() {
+        assert!(get_links("ces Main { a => b <= c }").is_empty());
+        assert!(get_links("ces Main { { a b -> c } + { c <- a b } }").is_empty());
+
+        assert_eq!(
+            get_links("ces A { a -> b -> a } ces Main { A() + { c => a } }"),
+            vec![
+                ("(b >? a)".to_owned(), Some("a -> b -> a".to_owned())),
+                ("(a ?> b)".to_owned(), Some("a -> b -> a".to_owned())),
+            ]
+        );
+    }
+}
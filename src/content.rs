@@ -43,7 +43,7 @@ impl ContentFormat for AscesisFormat {
 
         if let Some(word) = words.next() {
             match word {
-                "ces" => true,
+                "ces" | "use" => true,
                 _ => {
                     if word.contains('{') {
                         // Script starts with a word containing left brace.
@@ -102,6 +102,10 @@ impl ContentFormat for AscesisFormat {
     ) -> Result<Box<dyn Content>, Box<dyn Error>> {
         let mut ces_file = CesFile::from_script(script)?;
 
+        if let Some(ref path) = self.path {
+            ces_file.set_path(path);
+        }
+
         if let Some(root_name) = root_name {
             ces_file.set_root_name(root_name)?;
 
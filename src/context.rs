@@ -1,6 +1,9 @@
 use std::{collections::BTreeMap, convert::TryInto, cmp, fmt, error::Error};
 use aces::{ContextHandle, Compilable, Polarity, Capacity, Weight, sat};
-use crate::{Polynomial, DotName, DotList, Literal, AscesisError, AscesisErrorKind};
+use crate::{
+    Polynomial, DotName, DotList, ToDotName, Literal, Span, AscesisError, AscesisErrorKind,
+    AscesisWarning, AscesisWarningKind, spelling::find_similar, span::NodeSpan,
+};
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum PropSelector {
@@ -10,6 +13,17 @@ pub enum PropSelector {
     Invalid(String),
 }
 
+impl PropSelector {
+    /// Returns all keys recognized in blocks with this selector.
+    pub(crate) fn get_known_keys(&self) -> &'static [&'static str] {
+        match self {
+            PropSelector::Vis => &["labels", "title"],
+            PropSelector::SAT => &["encoding", "search"],
+            PropSelector::AnonymousBlock | PropSelector::Invalid(_) => &[],
+        }
+    }
+}
+
 impl Default for PropSelector {
     #[inline]
     fn default() -> Self {
@@ -30,6 +44,49 @@ impl fmt::Display for PropSelector {
     }
 }
 
+/// Renames keys of `map` found in `dot_map` keys, and returns the
+/// renamed map, where each value is paired with its original key.
+///
+/// Of values given to keys which become equal after renaming, the
+/// value of the alphabetically last original key is kept.  For each
+/// other value dropped, a warning naming both original keys is pushed
+/// to `warnings`, unless the values are equal.
+fn rename_keys<K, V>(
+    map: BTreeMap<K, V>,
+    dot_map: &BTreeMap<DotName, DotName>,
+    what: &str,
+    warnings: &mut Vec<AscesisWarning>,
+) -> BTreeMap<K, (K, V)>
+where
+    K: AsRef<str> + From<String> + Clone + Ord,
+    V: PartialEq,
+{
+    let mut result: BTreeMap<K, (K, V)> = BTreeMap::new();
+
+    for (key, value) in map {
+        let new_key = match dot_map.get(&key.to_dot()) {
+            Some(dot_name) => K::from(dot_name.as_ref().to_owned()),
+            None => key.clone(),
+        };
+
+        if let Some((old_key, old_value)) = result.insert(new_key.clone(), (key.clone(), value)) {
+            if result[&new_key].1 != old_value {
+                warnings.push(
+                    AscesisWarningKind::RenameConflict(
+                        what.to_owned(),
+                        old_key.to_dot(),
+                        key.to_dot(),
+                        new_key.to_dot(),
+                    )
+                    .into(),
+                );
+            }
+        }
+    }
+
+    result
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum PropValue {
     Literal(Literal),
@@ -72,6 +129,54 @@ impl PropValue {
     pub(crate) fn new_dot_list(names: Vec<String>) -> Result<Self, AscesisError> {
         Ok(PropValue::DotList(names.into()))
     }
+
+    /// Replaces single-identifier values naming a template argument
+    /// with the literal bound to that argument.
+    pub(crate) fn bind_literals(&mut self, literals: &BTreeMap<String, Literal>) {
+        let param = match self {
+            PropValue::Identifier(identifier) => Some(identifier.as_str()),
+            PropValue::DotList(dot_list) if dot_list.dot_names.len() == 1 => {
+                dot_list.dot_names.first().map(|n| n.as_ref())
+            }
+            _ => None,
+        };
+
+        if let Some(lit) = param.and_then(|param| literals.get(param)) {
+            *self = PropValue::Literal(lit.clone());
+        } else {
+            match self {
+                PropValue::Array(values) => {
+                    for value in values.iter_mut() {
+                        value.bind_literals(literals);
+                    }
+                }
+                PropValue::Block(block) => block.bind_literals(literals),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl fmt::Display for PropValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PropValue::Literal(lit) => lit.fmt(f),
+            PropValue::Identifier(identifier) => identifier.fmt(f),
+            PropValue::SizeList(lits) => {
+                let lits: Vec<_> = lits.iter().map(|lit| lit.to_string()).collect();
+
+                write!(f, "{}", lits.join(" "))
+            }
+            PropValue::IdentifierList(ids) => write!(f, "{}", ids.join(" ")),
+            PropValue::DotList(dot_list) => dot_list.fmt(f),
+            PropValue::Array(values) => {
+                let values: Vec<_> = values.iter().map(|value| value.to_string()).collect();
+
+                write!(f, "[{}]", values.join(", "))
+            }
+            PropValue::Block(block) => block.fmt(f),
+        }
+    }
 }
 
 impl From<PropBlock> for PropValue {
@@ -90,6 +195,7 @@ impl From<Vec<PropValue>> for PropValue {
 pub struct PropBlock {
     selector: PropSelector,
     fields:   BTreeMap<String, PropValue>,
+    span:     NodeSpan,
 }
 
 impl PropBlock {
@@ -98,6 +204,17 @@ impl PropBlock {
         Default::default()
     }
 
+    #[inline]
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span.into();
+        self
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        *self.span
+    }
+
     pub(crate) fn with_prop(mut self, key: String, value: PropValue) -> Self {
         self.fields.insert(key, value);
 
@@ -116,7 +233,8 @@ impl PropBlock {
 
     pub fn get_selector(&self) -> Result<PropSelector, AscesisError> {
         if let PropSelector::Invalid(ref selector) = self.selector {
-            Err(AscesisErrorKind::InvalidPropSelector(selector.to_owned()).into())
+            Err(AscesisError::from(AscesisErrorKind::InvalidPropSelector(selector.to_owned()))
+                .with_span(*self.span))
         } else {
             Ok(self.selector.clone())
         }
@@ -128,7 +246,8 @@ impl PropBlock {
         if actual == expected {
             Ok(())
         } else {
-            Err(AscesisErrorKind::BlockSelectorMismatch(expected, actual).into())
+            Err(AscesisError::from(AscesisErrorKind::BlockSelectorMismatch(expected, actual))
+                .with_span(*self.span))
         }
     }
 
@@ -139,6 +258,57 @@ impl PropBlock {
         self
     }
 
+    /// Renames dots labeled in a `vis` block.  Of different labels
+    /// given to dots which become equal after renaming, the label of
+    /// the alphabetically last dot is kept, and a warning is returned
+    /// for each label dropped.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) -> Vec<AscesisWarning> {
+        let mut warnings = Vec::new();
+
+        if self.selector == PropSelector::Vis {
+            if let Some(PropValue::Block(labels)) = self.fields.get_mut("labels") {
+                let fields = std::mem::take(&mut labels.fields);
+
+                labels.fields = rename_keys(fields, dot_map, "label", &mut warnings)
+                    .into_iter()
+                    .map(|(key, (_, value))| (key, value))
+                    .collect();
+            }
+        }
+
+        warnings.into_iter().map(|warning| warning.with_default_span(*self.span)).collect()
+    }
+
+    /// Returns names of all dots labeled in a `vis` block.
+    pub(crate) fn get_labeled_dot_names(&self) -> Vec<&str> {
+        match self.fields.get("labels") {
+            Some(PropValue::Block(labels)) if self.selector == PropSelector::Vis => {
+                labels.fields.keys().map(|key| key.as_str()).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns all keys of this block which aren't known for its
+    /// selector, each paired with the most similar known key, if any
+    /// is similar enough.
+    pub(crate) fn get_unknown_keys(&self) -> Vec<(&str, Option<&'static str>)> {
+        let known_keys = self.selector.get_known_keys();
+
+        self.fields
+            .keys()
+            .map(|key| key.as_str())
+            .filter(|key| !known_keys.contains(key))
+            .map(|key| (key, find_similar(key, known_keys.iter().copied())))
+            .collect()
+    }
+
+    pub(crate) fn bind_literals(&mut self, literals: &BTreeMap<String, Literal>) {
+        for value in self.fields.values_mut() {
+            value.bind_literals(literals);
+        }
+    }
+
     pub fn get<S: AsRef<str>>(&self, key: S) -> Option<&PropValue> {
         let key = key.as_ref();
 
@@ -339,6 +509,23 @@ impl PropBlock {
     }
 }
 
+/// Prints a `PropBlock` preceded by its selector.  A nested block has
+/// no selector and is printed as a braced list of fields.
+impl fmt::Display for PropBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keyword = match self.selector {
+            PropSelector::AnonymousBlock => None,
+            PropSelector::Vis => Some("vis"),
+            PropSelector::SAT => Some("sat"),
+            PropSelector::Invalid(ref name) => Some(name.as_str()),
+        };
+
+        let fields = self.fields.iter().map(|(key, value)| format!("{}: {}", key, value));
+
+        fmt_block(f, keyword, fields)
+    }
+}
+
 impl Compilable for PropBlock {
     fn compile(&self, ctx: &ContextHandle) -> Result<bool, Box<dyn Error>> {
         match self.get_selector()? {
@@ -389,9 +576,17 @@ impl Compilable for PropBlock {
 }
 
 /// A map from dots to their capacities.
+///
+/// A capacity may also be given by the name of a `Size` argument of
+/// the enclosing template.  It remains pending until the argument is
+/// bound, and compiling a block with a pending capacity fails.  The
+/// parser rejects capacities given by names other than those of
+/// `Size` arguments, in particular any outside of template bodies.
 #[derive(Clone, PartialEq, Eq, Default, Debug)]
 pub struct CapacitiesBlock {
-    capacities: BTreeMap<DotName, Capacity>,
+    capacities:  BTreeMap<DotName, Capacity>,
+    size_params: BTreeMap<DotName, String>,
+    span:        NodeSpan,
 }
 
 impl CapacitiesBlock {
@@ -400,36 +595,170 @@ impl CapacitiesBlock {
         Default::default()
     }
 
+    #[inline]
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span.into();
+        self
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        *self.span
+    }
+
     pub fn with_dot_names(
         mut self,
         size: Literal,
         dot_names: Polynomial,
     ) -> Result<Self, AscesisError> {
-        let capacity = match size {
-            Literal::Size(sz) => Capacity::finite(sz)
-                .ok_or_else(|| AscesisError::from(AscesisErrorKind::SizeLiteralOverflow))?,
-            Literal::Omega => Capacity::omega(),
-            _ => return Err(AscesisError::from(AscesisErrorKind::ExpectedSizeLiteral)),
-        };
+        let capacity = size.try_into()?;
         let dot_list: DotList = dot_names.try_into()?;
 
         for dot_name in dot_list.dot_names.into_iter() {
+            self.size_params.remove(&dot_name);
             self.capacities.insert(dot_name, capacity);
         }
 
         Ok(self)
     }
 
+    pub fn with_size_param(
+        mut self,
+        param: String,
+        dot_names: Polynomial,
+    ) -> Result<Self, AscesisError> {
+        let dot_list: DotList = dot_names.try_into()?;
+
+        for dot_name in dot_list.dot_names.into_iter() {
+            self.capacities.remove(&dot_name);
+            self.size_params.insert(dot_name, param.clone());
+        }
+
+        Ok(self)
+    }
+
     pub(crate) fn with_more(mut self, more: Vec<Self>) -> Self {
-        for mut block in more {
-            self.capacities.append(&mut block.capacities);
+        for block in more {
+            for (dot_name, capacity) in block.capacities {
+                self.size_params.remove(&dot_name);
+                self.capacities.insert(dot_name, capacity);
+            }
+
+            for (dot_name, param) in block.size_params {
+                self.capacities.remove(&dot_name);
+                self.size_params.insert(dot_name, param);
+            }
         }
         self
     }
+
+    /// Renames dots given a capacity.  Of different capacities given
+    /// to dots which become equal after renaming, the capacity of the
+    /// alphabetically last dot is kept, except that a constant
+    /// capacity takes precedence over a size parameter.  A warning is
+    /// returned for each capacity dropped.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) -> Vec<AscesisWarning> {
+        let mut warnings = Vec::new();
+        let capacities = std::mem::take(&mut self.capacities);
+        let size_params = std::mem::take(&mut self.size_params);
+        let capacities = rename_keys(capacities, dot_map, "capacity", &mut warnings);
+        let size_params = rename_keys(size_params, dot_map, "capacity", &mut warnings);
+
+        for (dot_name, (old_name, param)) in size_params {
+            if let Some((kept_name, _)) = capacities.get(&dot_name) {
+                warnings.push(
+                    AscesisWarningKind::RenameConflict(
+                        "capacity".into(),
+                        old_name.to_dot(),
+                        kept_name.to_dot(),
+                        dot_name.clone(),
+                    )
+                    .into(),
+                );
+            } else {
+                self.size_params.insert(dot_name, param);
+            }
+        }
+
+        self.capacities =
+            capacities.into_iter().map(|(dot_name, (_, capacity))| (dot_name, capacity)).collect();
+
+        warnings.into_iter().map(|warning| warning.with_default_span(*self.span)).collect()
+    }
+
+    /// Returns names of all dots given a capacity in this block.
+    pub(crate) fn get_dot_names(&self) -> Vec<&DotName> {
+        self.capacities.keys().chain(self.size_params.keys()).collect()
+    }
+
+    /// Fails if a capacity is given by a name for which `is_size_arg`
+    /// returns `false`.
+    pub(crate) fn check_size_params<F>(&self, is_size_arg: F) -> Result<(), AscesisError>
+    where
+        F: Fn(&str) -> bool,
+    {
+        match self.size_params.values().find(|param| !is_size_arg(param)) {
+            Some(param) => {
+                Err(AscesisError::from(AscesisErrorKind::SizeArgUndeclared(param.to_owned()))
+                    .with_span(*self.span))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Resolves pending capacities given by names of template
+    /// arguments bound to size literals.
+    pub(crate) fn bind_literals(
+        &mut self,
+        literals: &BTreeMap<String, Literal>,
+    ) -> Result<(), AscesisError> {
+        let size_params = std::mem::take(&mut self.size_params);
+
+        for (dot_name, param) in size_params {
+            if let Some(size) = literals.get(&param) {
+                self.capacities.insert(dot_name, size.clone().try_into()?);
+            } else {
+                self.size_params.insert(dot_name, param);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints a `CapacitiesBlock` with one field per distinct capacity,
+/// listing all dots of that capacity.
+impl fmt::Display for CapacitiesBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut fields: Vec<(String, Vec<&str>)> = Vec::new();
+
+        let capacities = self.capacities.iter().map(|(dot_name, cap)| (cap.to_string(), dot_name));
+        let size_params =
+            self.size_params.iter().map(|(dot_name, param)| (param.clone(), dot_name));
+
+        for (size, dot_name) in capacities.chain(size_params) {
+            if let Some((_, dot_names)) = fields.iter_mut().find(|(s, _)| *s == size) {
+                dot_names.push(dot_name.as_ref());
+            } else {
+                fields.push((size, vec![dot_name.as_ref()]));
+            }
+        }
+
+        let fields =
+            fields.iter().map(|(size, dot_names)| format!("{} {}", size, dot_names.join(" ")));
+
+        fmt_block(f, Some("caps"), fields)
+    }
 }
 
 impl Compilable for CapacitiesBlock {
     fn compile(&self, ctx: &ContextHandle) -> Result<bool, Box<dyn Error>> {
+        if let Some(param) = self.size_params.values().next() {
+            return Err(AscesisError::from(AscesisErrorKind::UnboundSizeArg(param.to_owned()))
+                .with_span(*self.span)
+                .into())
+        }
+
         let mut ctx = ctx.lock().unwrap();
 
         for (dot_name, cap) in self.capacities.iter() {
@@ -444,6 +773,7 @@ impl Compilable for CapacitiesBlock {
 #[derive(Clone, PartialEq, Eq, Default, Debug)]
 pub struct UnboundedBlock {
     dot_names: Vec<DotName>,
+    span:      NodeSpan,
 }
 
 impl UnboundedBlock {
@@ -452,10 +782,46 @@ impl UnboundedBlock {
         Default::default()
     }
 
+    #[inline]
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span.into();
+        self
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        *self.span
+    }
+
     pub fn from_dot_names(dot_names: Polynomial) -> Result<Self, AscesisError> {
         let dot_list: DotList = dot_names.try_into()?;
 
-        Ok(UnboundedBlock { dot_names: dot_list.dot_names })
+        Ok(UnboundedBlock { dot_names: dot_list.dot_names, ..Default::default() })
+    }
+
+    /// Renames unbounded dots, merging those which become equal.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) {
+        let mut dot_list = DotList { dot_names: std::mem::take(&mut self.dot_names) };
+
+        dot_list.rename_dots(dot_map);
+        self.dot_names = dot_list.dot_names;
+    }
+
+    #[inline]
+    pub(crate) fn get_dot_names(&self) -> Vec<&DotName> {
+        self.dot_names.iter().collect()
+    }
+}
+
+impl fmt::Display for UnboundedBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.dot_names.is_empty() {
+            write!(f, "unbounded {{}}")
+        } else {
+            let dot_names: Vec<_> = self.dot_names.iter().map(|n| n.as_ref()).collect();
+
+            write!(f, "unbounded {{ {} }}", dot_names.join(" "))
+        }
     }
 }
 
@@ -476,6 +842,7 @@ impl Compilable for UnboundedBlock {
 #[derive(Clone, PartialEq, Eq, Default, Debug)]
 pub struct WeightsBlock {
     xfer_multiplicities: Vec<XferMultiplicity>,
+    span:                NodeSpan,
 }
 
 impl WeightsBlock {
@@ -484,6 +851,17 @@ impl WeightsBlock {
         Default::default()
     }
 
+    #[inline]
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span.into();
+        self
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        *self.span
+    }
+
     pub fn new_join_weights(
         size: Literal,
         post_dots: Polynomial,
@@ -502,7 +880,7 @@ impl WeightsBlock {
             .collect();
         // No need to sort: `post_dots` are already ordered and deduplicated.
 
-        Ok(WeightsBlock { xfer_multiplicities })
+        Ok(WeightsBlock { xfer_multiplicities, ..Default::default() })
     }
 
     pub fn new_fork_weights(
@@ -523,7 +901,7 @@ impl WeightsBlock {
             .collect();
         // No need to sort: `pre_dots` are already ordered and deduplicated.
 
-        Ok(WeightsBlock { xfer_multiplicities })
+        Ok(WeightsBlock { xfer_multiplicities, ..Default::default() })
     }
 
     pub(crate) fn with_more(mut self, more: Vec<Self>) -> Self {
@@ -537,6 +915,71 @@ impl WeightsBlock {
 
         self
     }
+
+    /// Renames tips and arms of all weighted transfers.  Of the
+    /// transfers which become equal after renaming, the weight of the
+    /// alphabetically last one is kept, and a warning is returned for
+    /// each other weight dropped.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) -> Vec<AscesisWarning> {
+        let mut warnings: Vec<AscesisWarning> = Vec::new();
+        let mut xfers: Vec<_> = std::mem::take(&mut self.xfer_multiplicities)
+            .into_iter()
+            .map(|xfer| (xfer.renamed(dot_map), xfer))
+            .collect();
+
+        // Within each group of merged transfers, the first one kept by
+        // `dedup_by` is the alphabetically last before renaming.
+        xfers.sort_by(|(xfer, old_xfer), (other, old_other)| {
+            xfer.cmp_transfers(other).then_with(|| old_other.cmp(old_xfer))
+        });
+
+        xfers.dedup_by(|(xfer, old_xfer), (kept, old_kept)| {
+            let is_merged = xfer.cmp_transfers(kept) == cmp::Ordering::Equal;
+
+            if is_merged && xfer.get_weight() != kept.get_weight() {
+                if let Some((dropped_name, kept_name, new_name)) =
+                    old_xfer.find_renamed_pair(old_kept, dot_map)
+                {
+                    warnings.push(
+                        AscesisWarningKind::RenameConflict(
+                            "weight".into(),
+                            dropped_name,
+                            kept_name,
+                            new_name,
+                        )
+                        .into(),
+                    );
+                }
+            }
+
+            is_merged
+        });
+
+        self.xfer_multiplicities = xfers.into_iter().map(|(xfer, _)| xfer).collect();
+
+        warnings.into_iter().map(|warning| warning.with_default_span(*self.span)).collect()
+    }
+
+    /// Returns names of all dots, tips and arms, in this block.
+    pub(crate) fn get_dot_names(&self) -> Vec<&DotName> {
+        self.xfer_multiplicities
+            .iter()
+            .flat_map(|xfer| match xfer {
+                XferMultiplicity::Rx(rx) => {
+                    Some(&rx.tip_name).into_iter().chain(&rx.pre_arms.dot_names)
+                }
+                XferMultiplicity::Tx(tx) => {
+                    Some(&tx.tip_name).into_iter().chain(&tx.post_arms.dot_names)
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for WeightsBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_block(f, Some("weights"), self.xfer_multiplicities.iter())
+    }
 }
 
 impl Compilable for WeightsBlock {
@@ -578,6 +1021,76 @@ enum XferMultiplicity {
     Tx(TxWeight),
 }
 
+impl fmt::Display for XferMultiplicity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Rx(rx) => write!(f, "{} {} <- {}", rx.weight, rx.tip_name, rx.pre_arms),
+            Self::Tx(tx) => write!(f, "{} {} -> {}", tx.weight, tx.tip_name, tx.post_arms),
+        }
+    }
+}
+
+impl XferMultiplicity {
+    fn get_weight(&self) -> Weight {
+        match self {
+            Self::Rx(rx) => rx.weight,
+            Self::Tx(tx) => tx.weight,
+        }
+    }
+
+    fn get_tip_and_arms(&self) -> (&DotName, &DotList) {
+        match self {
+            Self::Rx(rx) => (&rx.tip_name, &rx.pre_arms),
+            Self::Tx(tx) => (&tx.tip_name, &tx.post_arms),
+        }
+    }
+
+    fn renamed(&self, dot_map: &BTreeMap<DotName, DotName>) -> Self {
+        let mut xfer = self.clone();
+
+        match &mut xfer {
+            Self::Rx(rx) => {
+                rename_tip(&mut rx.tip_name, dot_map);
+                rx.pre_arms.rename_dots(dot_map);
+            }
+            Self::Tx(tx) => {
+                rename_tip(&mut tx.tip_name, dot_map);
+                tx.post_arms.rename_dots(dot_map);
+            }
+        }
+
+        xfer
+    }
+
+    /// Compares transfers, ignoring their weights.
+    fn cmp_transfers(&self, other: &Self) -> cmp::Ordering {
+        match (self, other) {
+            (Self::Rx(_), Self::Tx(_)) => cmp::Ordering::Less,
+            (Self::Tx(_), Self::Rx(_)) => cmp::Ordering::Greater,
+            _ => self.get_tip_and_arms().cmp(&other.get_tip_and_arms()),
+        }
+    }
+
+    /// Given a transfer `kept`, different from this one, but equal to
+    /// it after renaming, returns a dot of this transfer and a dot of
+    /// `kept`, both renamed to the same name, followed by that name.
+    fn find_renamed_pair(
+        &self,
+        kept: &Self,
+        dot_map: &BTreeMap<DotName, DotName>,
+    ) -> Option<(DotName, DotName, DotName)> {
+        let (tip_name, arms) = self.get_tip_and_arms();
+        let (kept_tip_name, kept_arms) = kept.get_tip_and_arms();
+
+        find_renamed_pair(
+            std::slice::from_ref(tip_name),
+            std::slice::from_ref(kept_tip_name),
+            dot_map,
+        )
+        .or_else(|| find_renamed_pair(&arms.dot_names, &kept_arms.dot_names, dot_map))
+    }
+}
+
 impl cmp::Ord for XferMultiplicity {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
         match self {
@@ -653,6 +1166,7 @@ impl cmp::PartialOrd for TxWeight {
 #[derive(Clone, PartialEq, Eq, Default, Debug)]
 pub struct InhibitorsBlock {
     inhibitors: Vec<Inhibitor>,
+    span:       NodeSpan,
 }
 
 impl InhibitorsBlock {
@@ -661,6 +1175,17 @@ impl InhibitorsBlock {
         Default::default()
     }
 
+    #[inline]
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span.into();
+        self
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        *self.span
+    }
+
     pub fn new_causes(post_dots: Polynomial, pre_poly: Polynomial) -> Result<Self, AscesisError> {
         let post_dots: DotList = post_dots.try_into()?;
         let mut inhibitors = Vec::new();
@@ -676,7 +1201,7 @@ impl InhibitorsBlock {
             }
         }
 
-        Ok(InhibitorsBlock { inhibitors })
+        Ok(InhibitorsBlock { inhibitors, ..Default::default() })
     }
 
     pub fn new_effects(pre_dots: Polynomial, post_poly: Polynomial) -> Result<Self, AscesisError> {
@@ -694,7 +1219,7 @@ impl InhibitorsBlock {
             }
         }
 
-        Ok(InhibitorsBlock { inhibitors })
+        Ok(InhibitorsBlock { inhibitors, ..Default::default() })
     }
 
     pub(crate) fn with_more(mut self, more: Vec<Self>) -> Self {
@@ -708,6 +1233,45 @@ impl InhibitorsBlock {
 
         self
     }
+
+    /// Renames tips and arms of all inhibitors, keeping the block
+    /// sorted and free of duplicates.  Inhibitors carry no values, so
+    /// merging those which become equal drops nothing.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) {
+        for inhibitor in self.inhibitors.iter_mut() {
+            match inhibitor {
+                Inhibitor::Rx(rx) => {
+                    rename_tip(&mut rx.post_tip, dot_map);
+                    rx.pre_arms.rename_dots(dot_map);
+                }
+                Inhibitor::Tx(tx) => {
+                    rename_tip(&mut tx.pre_tip, dot_map);
+                    tx.post_arms.rename_dots(dot_map);
+                }
+            }
+        }
+
+        self.inhibitors.sort();
+        let len = self.inhibitors.partition_dedup().0.len();
+        self.inhibitors.truncate(len);
+    }
+
+    /// Returns names of all dots, tips and arms, in this block.
+    pub(crate) fn get_dot_names(&self) -> Vec<&DotName> {
+        self.inhibitors
+            .iter()
+            .flat_map(|inhibitor| match inhibitor {
+                Inhibitor::Rx(rx) => Some(&rx.post_tip).into_iter().chain(&rx.pre_arms.dot_names),
+                Inhibitor::Tx(tx) => Some(&tx.pre_tip).into_iter().chain(&tx.post_arms.dot_names),
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for InhibitorsBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_block(f, Some("inhibit"), self.inhibitors.iter())
+    }
 }
 
 impl Compilable for InhibitorsBlock {
@@ -739,6 +1303,15 @@ pub enum Inhibitor {
     Tx(TxInhibitor),
 }
 
+impl fmt::Display for Inhibitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Rx(rx) => write!(f, "{} <- {}", rx.post_tip, rx.pre_arms),
+            Self::Tx(tx) => write!(f, "{} -> {}", tx.pre_tip, tx.post_arms),
+        }
+    }
+}
+
 impl cmp::Ord for Inhibitor {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
         match self {
@@ -807,6 +1380,7 @@ impl cmp::PartialOrd for TxInhibitor {
 pub struct WeightlessBlock {
     pub(crate) polarity: Option<Polarity>,
     pub(crate) splits:   Vec<Weightless>,
+    span:                NodeSpan,
 }
 
 impl WeightlessBlock {
@@ -815,6 +1389,17 @@ impl WeightlessBlock {
         Default::default()
     }
 
+    #[inline]
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span.into();
+        self
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        *self.span
+    }
+
     pub fn new_causes(post_dots: Polynomial, pre_poly: Polynomial) -> Result<Self, AscesisError> {
         let polarity = Some(Polarity::Rx);
         let post_dots: DotList = post_dots.try_into()?;
@@ -831,7 +1416,7 @@ impl WeightlessBlock {
             }
         }
 
-        Ok(WeightlessBlock { polarity, splits })
+        Ok(WeightlessBlock { polarity, splits, ..Default::default() })
     }
 
     pub fn new_effects(pre_dots: Polynomial, post_poly: Polynomial) -> Result<Self, AscesisError> {
@@ -850,7 +1435,7 @@ impl WeightlessBlock {
             }
         }
 
-        Ok(WeightlessBlock { polarity, splits })
+        Ok(WeightlessBlock { polarity, splits, ..Default::default() })
     }
 
     pub(crate) fn with_more(mut self, more: Vec<Self>) -> Self {
@@ -869,6 +1454,42 @@ impl WeightlessBlock {
         self
     }
 
+    /// Renames tips and arms of all splits, keeping the block sorted
+    /// and free of duplicates.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) {
+        for split in self.splits.iter_mut() {
+            match split {
+                Weightless::Activate(tx) => {
+                    rename_tip(&mut tx.pre_tip, dot_map);
+                    tx.post_arms.rename_dots(dot_map);
+                }
+                Weightless::Drop(rx) => {
+                    rename_tip(&mut rx.post_tip, dot_map);
+                    rx.pre_arms.rename_dots(dot_map);
+                }
+            }
+        }
+
+        self.splits.sort();
+        let len = self.splits.partition_dedup().0.len();
+        self.splits.truncate(len);
+    }
+
+    /// Returns names of all dots, tips and arms, in this block.
+    pub(crate) fn get_dot_names(&self) -> Vec<&DotName> {
+        self.splits
+            .iter()
+            .flat_map(|split| match split {
+                Weightless::Activate(tx) => {
+                    Some(&tx.pre_tip).into_iter().chain(&tx.post_arms.dot_names)
+                }
+                Weightless::Drop(rx) => {
+                    Some(&rx.post_tip).into_iter().chain(&rx.pre_arms.dot_names)
+                }
+            })
+            .collect()
+    }
+
     #[inline]
     pub fn get_polarity(&self) -> Option<Polarity> {
         self.polarity
@@ -905,7 +1526,28 @@ impl From<WeightlessBlock> for WeightsBlock {
             }
         }
 
-        WeightsBlock::new().with_more(more_weights)
+        WeightsBlock::new().with_more(more_weights).with_span(*block.span)
+    }
+}
+
+/// Prints a `WeightlessBlock` as an `activate` or a `drop` block,
+/// depending on its polarity.  A block mixing both kinds of splits,
+/// as merged by [`WeightlessBlock::with_more`], is printed as an
+/// `activate` block followed by a `drop` block.
+impl fmt::Display for WeightlessBlock {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (activators, drops): (Vec<_>, Vec<_>) =
+            self.splits.iter().partition(|split| matches!(split, Weightless::Activate(_)));
+
+        if drops.is_empty() && self.polarity != Some(Polarity::Rx) {
+            fmt_block(f, Some("activate"), activators)
+        } else if activators.is_empty() {
+            fmt_block(f, Some("drop"), drops)
+        } else {
+            fmt_block(f, Some("activate"), activators)?;
+            writeln!(f)?;
+            fmt_block(f, Some("drop"), drops)
+        }
     }
 }
 
@@ -938,6 +1580,15 @@ pub enum Weightless {
     Drop(RxWeightless),
 }
 
+impl fmt::Display for Weightless {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Activate(tx) => write!(f, "{} -> {}", tx.pre_tip, tx.post_arms),
+            Self::Drop(rx) => write!(f, "{} <- {}", rx.post_tip, rx.pre_arms),
+        }
+    }
+}
+
 impl cmp::Ord for Weightless {
     fn cmp(&self, other: &Self) -> cmp::Ordering {
         match self {
@@ -1000,3 +1651,58 @@ impl cmp::PartialOrd for RxWeightless {
         Some(self.cmp(other))
     }
 }
+
+/// Writes a block of comma-separated `fields`, preceded by a
+/// `keyword`, if given.
+fn fmt_block<I>(f: &mut fmt::Formatter, keyword: Option<&str>, fields: I) -> fmt::Result
+where
+    I: IntoIterator,
+    I::Item: fmt::Display,
+{
+    let fields: Vec<_> = fields.into_iter().map(|field| field.to_string()).collect();
+
+    if let Some(keyword) = keyword {
+        write!(f, "{} ", keyword)?;
+    }
+
+    if fields.is_empty() {
+        write!(f, "{{}}")
+    } else {
+        write!(f, "{{ {} }}", fields.join(", "))
+    }
+}
+
+fn rename_tip(tip_name: &mut DotName, dot_map: &BTreeMap<DotName, DotName>) {
+    if let Some(new_name) = dot_map.get(tip_name) {
+        *tip_name = new_name.clone();
+    }
+}
+
+/// Given two lists of dots, `dropped` and `kept`, which are different,
+/// but equal after renaming, returns a dot of `dropped` and a dot of
+/// `kept`, both renamed to the same name, followed by that name.
+fn find_renamed_pair(
+    dropped: &[DotName],
+    kept: &[DotName],
+    dot_map: &BTreeMap<DotName, DotName>,
+) -> Option<(DotName, DotName, DotName)> {
+    let rename = |dot_name: &DotName| dot_map.get(dot_name).unwrap_or(dot_name).clone();
+
+    for dot_name in dropped.iter().filter(|dot_name| !kept.contains(dot_name)) {
+        let new_name = rename(dot_name);
+
+        if let Some(kept_name) = kept.iter().find(|kept_name| rename(kept_name) == new_name) {
+            return Some((dot_name.clone(), kept_name.clone(), new_name))
+        }
+    }
+
+    for kept_name in kept.iter().filter(|kept_name| !dropped.contains(kept_name)) {
+        let new_name = rename(kept_name);
+
+        if let Some(dot_name) = dropped.iter().find(|dot_name| rename(dot_name) == new_name) {
+            return Some((dot_name.clone(), kept_name.clone(), new_name))
+        }
+    }
+
+    None
+}
@@ -0,0 +1,474 @@
+use std::fmt::{self, Write};
+use crate::{Span, span::line_column};
+
+/// Severity of a [`Diagnostic`].
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A span of source text, optionally annotated with a message.
+///
+/// The primary label of a [`Diagnostic`] points at the place where
+/// the problem was detected, secondary labels point at related
+/// places, e.g. a previous definition.
+#[derive(Clone, Debug)]
+pub struct Label {
+    span:       Span,
+    message:    Option<String>,
+    is_primary: bool,
+}
+
+impl Label {
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+
+    #[inline]
+    pub fn get_message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    #[inline]
+    pub fn is_primary(&self) -> bool {
+        self.is_primary
+    }
+}
+
+/// A suggested fix: replacement of the source text in a span.  An
+/// empty span denotes insertion.
+#[derive(Clone, Debug)]
+pub struct Suggestion {
+    span:        Span,
+    replacement: String,
+    message:     String,
+}
+
+impl Suggestion {
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+
+    #[inline]
+    pub fn get_replacement(&self) -> &str {
+        self.replacement.as_str()
+    }
+
+    #[inline]
+    pub fn get_message(&self) -> &str {
+        self.message.as_str()
+    }
+}
+
+/// A problem found in a script, described in a form suitable both
+/// for reading and for processing by tools.
+///
+/// A `Diagnostic` refers to the script it was found in only through
+/// spans, hence the script has to be supplied for
+/// [`render()`](Diagnostic::render)ing source snippets.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    severity:    Severity,
+    code:        Option<&'static str>,
+    message:     String,
+    labels:      Vec<Label>,
+    notes:       Vec<String>,
+    suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new<S: Into<String>>(severity: Severity, message: S) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn error<S: Into<String>>(message: S) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    #[inline]
+    pub fn warning<S: Into<String>>(message: S) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Sets the primary label, replacing the previous one, if any.
+    pub fn with_primary_label(mut self, span: Span, message: Option<String>) -> Self {
+        self.labels.retain(|label| !label.is_primary);
+        self.labels.insert(0, Label { span, message, is_primary: true });
+        self
+    }
+
+    pub fn with_secondary_label<S: Into<String>>(mut self, span: Span, message: S) -> Self {
+        self.labels.push(Label { span, message: Some(message.into()), is_primary: false });
+        self
+    }
+
+    pub fn with_note<S: Into<String>>(mut self, note: S) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_suggestion<S, T>(mut self, span: Span, replacement: S, message: T) -> Self
+    where
+        S: Into<String>,
+        T: Into<String>,
+    {
+        self.suggestions.push(Suggestion {
+            span,
+            replacement: replacement.into(),
+            message: message.into(),
+        });
+        self
+    }
+
+    #[inline]
+    pub fn get_severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Returns the stable code identifying the kind of this
+    /// diagnostic, e.g. `E0210` for an undefined structure.
+    #[inline]
+    pub fn get_code(&self) -> Option<&'static str> {
+        self.code
+    }
+
+    #[inline]
+    pub fn get_message(&self) -> &str {
+        self.message.as_str()
+    }
+
+    /// Returns the span of the primary label, if any.
+    pub fn get_primary_span(&self) -> Option<Span> {
+        self.labels.iter().find(|label| label.is_primary).map(|label| label.span)
+    }
+
+    #[inline]
+    pub fn get_labels(&self) -> &[Label] {
+        self.labels.as_slice()
+    }
+
+    #[inline]
+    pub fn get_notes(&self) -> &[String] {
+        self.notes.as_slice()
+    }
+
+    #[inline]
+    pub fn get_suggestions(&self) -> &[Suggestion] {
+        self.suggestions.as_slice()
+    }
+
+    /// Renders this diagnostic in the style of `rustc`, with source
+    /// snippets of all labelled lines taken from `script`, which is
+    /// referred to as `origin`.  Without a script, only the message,
+    /// notes and suggestions are rendered.
+    pub fn render(&self, script: Option<&str>, origin: Option<&str>) -> String {
+        let mut result = String::new();
+
+        self.render_into(&mut result, script, origin).unwrap();
+
+        result
+    }
+
+    fn render_into(
+        &self,
+        out: &mut String,
+        script: Option<&str>,
+        origin: Option<&str>,
+    ) -> fmt::Result {
+        write!(out, "{}", self.severity)?;
+
+        if let Some(code) = self.code {
+            write!(out, "[{}]", code)?;
+        }
+
+        writeln!(out, ": {}", self.message)?;
+
+        let script = script.filter(|_| !self.labels.is_empty());
+        let mut gutter = String::new();
+
+        if let Some(script) = script {
+            let mut labels: Vec<_> = self
+                .labels
+                .iter()
+                .map(|label| (line_column(script, label.span.start()), label))
+                .collect();
+
+            labels.sort_by_key(|&((line, column), label)| (line, !label.is_primary, column));
+
+            let max_line = labels.iter().map(|((line, _), _)| *line).max().unwrap_or(1);
+
+            gutter = " ".repeat(max_line.to_string().len());
+
+            let (line, column) = line_column(script, self.labels[0].span.start());
+
+            writeln!(out, "{}--> {}:{}:{}", gutter, origin.unwrap_or("<script>"), line, column)?;
+            writeln!(out, "{} |", gutter)?;
+
+            let lines: Vec<_> = script.split('\n').collect();
+            let mut last_line = None;
+
+            for ((line, column), label) in labels {
+                let text = lines.get(line - 1).copied().unwrap_or("");
+
+                if last_line != Some(line) {
+                    if matches!(last_line, Some(last) if line > last + 1) {
+                        writeln!(out, "{}...", gutter)?;
+                    }
+                    writeln!(out, "{:>width$} | {}", line, text, width = gutter.len())?;
+                    last_line = Some(line);
+                }
+
+                // Spans running past the end of a line are underlined
+                // up to the end of that line.
+                let (end_line, end_column) = line_column(script, label.span.end());
+                let end_column =
+                    if end_line > line { text.chars().count() + 1 } else { end_column };
+                let width = end_column.saturating_sub(column).max(1);
+                let marker = if label.is_primary { "^" } else { "-" };
+
+                write!(out, "{} | {}{}", gutter, " ".repeat(column - 1), marker.repeat(width))?;
+
+                if let Some(ref message) = label.message {
+                    write!(out, " {}", message)?;
+                }
+                writeln!(out)?;
+            }
+        }
+
+        if !self.notes.is_empty() || !self.suggestions.is_empty() {
+            if script.is_some() {
+                writeln!(out, "{} |", gutter)?;
+            }
+
+            for note in self.notes.iter() {
+                writeln!(out, "{} = note: {}", gutter, note)?;
+            }
+
+            for suggestion in self.suggestions.iter() {
+                write!(
+                    out,
+                    "{} = help: {}: `{}`",
+                    gutter, suggestion.message, suggestion.replacement
+                )?;
+
+                if let Some(script) = script {
+                    let (line, column) = line_column(script, suggestion.span.start());
+
+                    write!(out, " at {}:{}", line, column)?;
+                }
+                writeln!(out)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Serialises this diagnostic as a single-line JSON object.  If
+    /// `script` is given, each span includes one-based line and column
+    /// numbers of its ends, in addition to byte offsets.
+    pub fn to_json(&self, script: Option<&str>) -> String {
+        let labels: Vec<_> = self
+            .labels
+            .iter()
+            .map(|label| {
+                format!(
+                    "{{\"span\":{},\"message\":{},\"is_primary\":{}}}",
+                    span_to_json(label.span, script),
+                    label.message.as_deref().map_or_else(|| "null".to_owned(), quote_json),
+                    label.is_primary
+                )
+            })
+            .collect();
+        let notes: Vec<_> = self.notes.iter().map(|note| quote_json(note)).collect();
+        let suggestions: Vec<_> = self
+            .suggestions
+            .iter()
+            .map(|suggestion| {
+                format!(
+                    "{{\"span\":{},\"replacement\":{},\"message\":{}}}",
+                    span_to_json(suggestion.span, script),
+                    quote_json(&suggestion.replacement),
+                    quote_json(&suggestion.message)
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"severity\":\"{}\",\"code\":{},\"message\":{},\"labels\":[{}],\"notes\":[{}],\"\
+             suggestions\":[{}]}}",
+            self.severity,
+            self.code.map_or_else(|| "null".to_owned(), quote_json),
+            quote_json(&self.message),
+            labels.join(","),
+            notes.join(","),
+            suggestions.join(",")
+        )
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.render(None, None))
+    }
+}
+
+fn span_to_json(span: Span, script: Option<&str>) -> String {
+    if let Some(script) = script {
+        let (line_start, column_start) = line_column(script, span.start());
+        let (line_end, column_end) = line_column(script, span.end().max(span.start()));
+
+        format!(
+            "{{\"start\":{},\"end\":{},\"line_start\":{},\"column_start\":{},\"line_end\":{},\"\
+             column_end\":{}}}",
+            span.start(),
+            span.end(),
+            line_start,
+            column_start,
+            line_end,
+            column_end
+        )
+    } else {
+        format!("{{\"start\":{},\"end\":{}}}", span.start(), span.end())
+    }
+}
+
+fn quote_json(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+
+    result.push('"');
+
+    for ch in text.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => write!(result, "\\u{:04x}", ch as u32).unwrap(),
+            ch => result.push(ch),
+        }
+    }
+
+    result.push('"');
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CesFile;
+
+    #[test]
+    fn test_render() {
+        let script = "ces Main {\n    { a => b } + Missing()\n}\n";
+        let err = CesFile::from_script(script).and_then(|mut ces_file| {
+            ces_file.set_root_name("Main")?;
+            aces::CompilableMut::compile_mut(&mut ces_file, &aces::Context::new_toplevel("Main"))
+        });
+        let err = err.unwrap_err().downcast::<crate::AscesisError>().unwrap();
+        let diagnostics = err.to_diagnostics();
+
+        assert_eq!(
+            diagnostics[0].render(Some(script), Some("main.ces")),
+            "error[E0210]: Undefined structure 'Missing'
+ --> main.ces:2:18
+  |
+2 |     { a => b } + Missing()
+  |                  ^^^^^^^^^ not defined
+"
+        );
+        assert_eq!(
+            diagnostics[0].to_json(Some(script)),
+            "{\"severity\":\"error\",\"code\":\"E0210\",\"message\":\"Undefined structure \
+             'Missing'\",\"labels\":[{\"span\":{\"start\":28,\"end\":37,\"line_start\":2,\"\
+             column_start\":18,\"line_end\":2,\"column_end\":27},\"message\":\"not \
+             defined\",\"is_primary\":true}],\"notes\":[],\"suggestions\":[]}"
+        );
+    }
+
+    #[test]
+    fn test_suggestion() {
+        let script = "ces Main { a -> b\n";
+        let err = CesFile::from_script(script).unwrap_err();
+        let err = err.downcast::<crate::AscesisError>().unwrap();
+        let diagnostics = err.to_diagnostics();
+        let rendered = diagnostics[0].render(Some(script), None);
+
+        assert!(rendered.starts_with("error[E0102]: Unexpected end of file\n"), "{}", rendered);
+        assert!(
+            rendered.contains("\n  = help: insert the missing delimiter: `}` at 1:18"),
+            "{}",
+            rendered
+        );
+    }
+
+    fn compile(script: &str) -> Vec<super::Diagnostic> {
+        let err = CesFile::from_script(script).and_then(|mut ces_file| {
+            ces_file.set_root_name("Main")?;
+            aces::CompilableMut::compile_mut(&mut ces_file, &aces::Context::new_toplevel("Main"))
+        });
+
+        err.unwrap_err().downcast::<crate::AscesisError>().unwrap().to_diagnostics()
+    }
+
+    #[test]
+    fn test_secondary_labels() {
+        let labels = |diagnostic: &super::Diagnostic| -> Vec<_> {
+            diagnostic
+                .get_labels()
+                .iter()
+                .map(|label| (label.get_span().start(), label.get_message().unwrap().to_owned()))
+                .collect()
+        };
+
+        let diagnostics = compile("ces A { a => b }\nces Main { A() }\nces A { b => c }\n");
+
+        assert_eq!(diagnostics[0].get_code(), Some("E0218"));
+        assert_eq!(
+            labels(&diagnostics[0]),
+            vec![(34, "redefined here".to_owned()), (0, "first defined here".to_owned())]
+        );
+
+        let diagnostics = compile("ces Main { A() }\nces A { B() }\nces B { A() }\n");
+
+        assert_eq!(diagnostics[0].get_code(), Some("E0211"));
+        assert_eq!(
+            labels(&diagnostics[0]),
+            vec![(39, "closes the cycle".to_owned()), (17, "'A' is defined here".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_lexing_failure() {
+        let kind = crate::AscesisErrorKind::LexingFailure("$".into(), 11..12);
+
+        assert_eq!(kind.to_string(), "Invalid token \"$\" at 11..12");
+    }
+}
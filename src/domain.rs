@@ -1,4 +1,9 @@
-use std::{collections::BTreeSet, convert::TryFrom, iter::FromIterator};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    iter::FromIterator,
+    fmt,
+};
 use crate::{Polynomial, AscesisError, AscesisErrorKind};
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
@@ -16,6 +21,13 @@ impl AsRef<str> for DotName {
     }
 }
 
+impl fmt::Display for DotName {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 pub trait ToDotName {
     fn to_dot(&self) -> DotName;
 }
@@ -47,6 +59,28 @@ impl DotList {
         let len = self.dot_names.partition_dedup().0.len();
         self.dot_names.truncate(len);
     }
+
+    /// Renames listed dots, keeping the list sorted and free of
+    /// duplicates.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) {
+        for dot_name in self.dot_names.iter_mut() {
+            if let Some(new_name) = dot_map.get(dot_name) {
+                *dot_name = new_name.clone();
+            }
+        }
+
+        self.dot_names.sort();
+        let len = self.dot_names.partition_dedup().0.len();
+        self.dot_names.truncate(len);
+    }
+}
+
+impl fmt::Display for DotList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dot_names: Vec<_> = self.dot_names.iter().map(|n| n.as_ref()).collect();
+
+        write!(f, "{}", dot_names.join(" "))
+    }
 }
 
 impl From<DotName> for DotList {
@@ -78,6 +112,8 @@ impl TryFrom<Polynomial> for DotList {
     type Error = AscesisError;
 
     fn try_from(poly: Polynomial) -> Result<Self, Self::Error> {
+        let span = poly.span;
+
         if poly.is_flat {
             let mut monomials = poly.monomials.into_iter();
 
@@ -89,13 +125,13 @@ impl TryFrom<Polynomial> for DotList {
                 if monomials.next().is_none() {
                     Ok(DotList { dot_names })
                 } else {
-                    Err(AscesisErrorKind::NotADotList.into())
+                    Err(AscesisError::from(AscesisErrorKind::NotADotList).with_span(*span))
                 }
             } else {
                 Ok(Default::default())
             }
         } else {
-            Err(AscesisErrorKind::NotADotList.into())
+            Err(AscesisError::from(AscesisErrorKind::NotADotList).with_span(*span))
         }
     }
 }
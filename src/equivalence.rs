@@ -0,0 +1,195 @@
+//! Semantic comparison of compiled structures.
+//!
+//! Two structures are equivalent if every dot has the same cause
+//! polynomial and the same effect polynomial in both of them.  Since
+//! structures may be compiled in different contexts, dots are
+//! identified by their names, not by their [`DotId`]s.
+
+use std::{collections::BTreeMap, fmt, error::Error};
+use aces::{ContextHandle, Content, CompilableAsContent, DotId, Polarity};
+use crate::{Rex, Polynomial, DotName, ToDotName};
+
+/// The first difference found between two compiled structures: a dot,
+/// the polarity of its differing polynomials, i.e. [`Polarity::Rx`]
+/// for causes and [`Polarity::Tx`] for effects, and both polynomials.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Difference {
+    dot_name: DotName,
+    polarity: Polarity,
+    lhs:      Polynomial,
+    rhs:      Polynomial,
+}
+
+impl Difference {
+    #[inline]
+    pub fn get_dot_name(&self) -> &DotName {
+        &self.dot_name
+    }
+
+    #[inline]
+    pub fn get_polarity(&self) -> Polarity {
+        self.polarity
+    }
+
+    /// Returns the differing polynomials, first of the left-hand side
+    /// structure, then of the right-hand side one.
+    #[inline]
+    pub fn get_polynomials(&self) -> (&Polynomial, &Polynomial) {
+        (&self.lhs, &self.rhs)
+    }
+}
+
+/// Prints a `Difference` naming the dot and both polynomials, where
+/// an empty polynomial is printed as `θ`.
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let fmt_poly = |poly: &Polynomial| {
+            if poly.is_empty() {
+                "θ".to_owned()
+            } else {
+                poly.to_string()
+            }
+        };
+
+        write!(
+            f,
+            "{} of dot '{}' differ: {} vs. {}",
+            if self.polarity == Polarity::Rx { "causes" } else { "effects" },
+            self.dot_name,
+            fmt_poly(&self.lhs),
+            fmt_poly(&self.rhs),
+        )
+    }
+}
+
+/// Cause and effect polynomials of each dot of a compiled structure,
+/// keyed by dot name.
+pub(crate) type DotPolynomials = BTreeMap<DotName, (Polynomial, Polynomial)>;
+
+pub(crate) fn get_dot_polynomials<C>(content: &mut C, ctx: &ContextHandle) -> DotPolynomials
+where
+    C: Content + ?Sized,
+{
+    let ctx = ctx.lock().unwrap();
+    let to_polynomial = |poly: Option<&Vec<Vec<DotId>>>| {
+        let mut poly = Polynomial {
+            monomials: poly
+                .into_iter()
+                .flatten()
+                .map(|mono| {
+                    mono.iter().map(|&id| ctx.get_dot_name(id).unwrap_or("?").to_dot()).collect()
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        poly.normalize();
+        poly
+    };
+
+    content
+        .get_carrier_ids()
+        .into_iter()
+        .map(|id| {
+            let dot_name = ctx.get_dot_name(id).unwrap_or("?").to_dot();
+            let causes = to_polynomial(content.get_causes_by_id(id));
+            let effects = to_polynomial(content.get_effects_by_id(id));
+
+            (dot_name, (causes, effects))
+        })
+        .collect()
+}
+
+/// Compares two compiled structures, `lhs` compiled in `lhs_ctx` and
+/// `rhs` compiled in `rhs_ctx`, which may be the same context.
+///
+/// Returns `None` if the structures are equivalent, or the difference
+/// found at the alphabetically first dot otherwise.  At each dot,
+/// causes are compared before effects.  A dot missing from one of the
+/// structures is treated as having no causes and no effects.
+pub fn compare_contents<C, D>(
+    lhs: &mut C,
+    lhs_ctx: &ContextHandle,
+    rhs: &mut D,
+    rhs_ctx: &ContextHandle,
+) -> Option<Difference>
+where
+    C: Content + ?Sized,
+    D: Content + ?Sized,
+{
+    let mut lhs = get_dot_polynomials(lhs, lhs_ctx);
+    let mut rhs = get_dot_polynomials(rhs, rhs_ctx);
+    let mut dot_names: Vec<_> = lhs.keys().chain(rhs.keys()).cloned().collect();
+
+    dot_names.sort();
+    dot_names.dedup();
+
+    for dot_name in dot_names {
+        let (lhs_causes, lhs_effects) = lhs.remove(&dot_name).unwrap_or_default();
+        let (rhs_causes, rhs_effects) = rhs.remove(&dot_name).unwrap_or_default();
+
+        if lhs_causes != rhs_causes {
+            return Some(Difference {
+                dot_name,
+                polarity: Polarity::Rx,
+                lhs: lhs_causes,
+                rhs: rhs_causes,
+            })
+        }
+
+        if lhs_effects != rhs_effects {
+            return Some(Difference {
+                dot_name,
+                polarity: Polarity::Tx,
+                lhs: lhs_effects,
+                rhs: rhs_effects,
+            })
+        }
+    }
+
+    None
+}
+
+/// Compiles both rule expressions in `ctx`, which must contain all
+/// structures they instantiate, and compares the results.
+///
+/// See [`compare_contents`].
+pub fn compare_rexes(
+    lhs: &Rex,
+    rhs: &Rex,
+    ctx: &ContextHandle,
+) -> Result<Option<Difference>, Box<dyn Error>> {
+    let mut lhs_content = lhs.get_compiled_content(ctx)?;
+    let mut rhs_content = rhs.get_compiled_content(ctx)?;
+
+    Ok(compare_contents(&mut lhs_content, ctx, &mut rhs_content, ctx))
+}
+
+#[cfg(test)]
+mod tests {
+    use aces::Context;
+    use super::*;
+
+    fn compare(lhs: &str, rhs: &str) -> Option<String> {
+        let ctx = Context::new_toplevel("equivalence");
+        let lhs: Rex = lhs.parse().unwrap();
+        let rhs: Rex = rhs.parse().unwrap();
+
+        compare_rexes(&lhs, &rhs, &ctx).unwrap().map(|diff| diff.to_string())
+    }
+
+    #[test]
+    fn test_equivalence() {
+        assert_eq!(compare("a => b", "{ a -> b } + { b <- a }"), None);
+        assert_eq!(compare("b <= a => c", "{ a -> b + c } + { b c <- a }"), None);
+        assert_eq!(compare("{ a -> b } { a -> c }", "a -> b c"), None);
+        assert_eq!(
+            compare("a => b", "a -> b"),
+            Some("causes of dot 'b' differ: a vs. θ".to_owned())
+        );
+        assert_eq!(
+            compare("a => b + c", "a => b"),
+            Some("effects of dot 'a' differ: b + c vs. b".to_owned())
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use std::{fmt, num::ParseIntError, error::Error};
-use crate::{PropSelector, Token};
+use aces::Polarity;
+use crate::{PropSelector, ArgType, DotName, Lexer, Token, Span, Diagnostic, span::line_column};
 
 pub(crate) type ParserError = lalrpop_util::ParseError<usize, String, AscesisError>;
 pub(crate) type RawParserError<'input> =
@@ -19,61 +20,17 @@ impl<'input> From<AscesisError> for RawParserError<'input> {
     }
 }
 
-fn format_location(mut pos: usize, script: &str) -> String {
-    for (num_lines, line) in script.lines().enumerate() {
-        match pos.checked_sub(line.len() + 1) {
-            Some(p) => pos = p,
-            None => return format!("[{}:{}]", num_lines + 1, pos + 1),
-        }
-    }
+fn format_location(pos: usize, script: &str) -> String {
+    let (line, column) = line_column(script, pos);
 
-    // FIXME
-    "<outside>".into()
+    format!("[{}:{}]", line, column)
 }
 
 fn format_span(span: &logos::Span, script: &str) -> String {
-    let mut start_location = None;
-    let mut end_location = None;
-    let mut pos = span.start;
-
-    for (num_lines, line) in script.lines().enumerate() {
-        match pos.checked_sub(line.len() + 1) {
-            Some(p) => pos = p,
-            None => {
-                if start_location.is_none() {
-                    start_location = Some((num_lines + 1, pos + 1));
-
-                    if span.end > span.start {
-                        let remaining = line.len() + 1 - pos;
-                        let end_pos = span.end - span.start;
-
-                        if end_pos > remaining {
-                            pos = end_pos - remaining;
-                        } else {
-                            end_location = Some((num_lines + 1, pos + end_pos + 1));
-                            break
-                        }
-                    } else {
-                        break
-                    }
-                } else {
-                    end_location = Some((num_lines + 1, pos + 1));
-                    break
-                }
-            }
-        }
-    }
-
-    if let Some((start_line, start_pos)) = start_location {
-        if let Some((end_line, end_pos)) = end_location {
-            format!("[{}:{}]..[{}:{}]", start_line, start_pos, end_line, end_pos)
-        } else {
-            // FIXME
-            format!("[{}:{}]", start_line, start_pos)
-        }
+    if span.end > span.start {
+        format!("{}..{}", format_location(span.start, script), format_location(span.end, script))
     } else {
-        // FIXME
-        "<empty-span>".into()
+        format_location(span.start, script)
     }
 }
 
@@ -84,7 +41,11 @@ fn display_parsing_recovery(
 ) -> fmt::Result {
     for (num, err) in errors.iter().enumerate() {
         let message = if let Some(script) = script {
-            format!("{}", err.clone().map_location(|pos| format_location(pos, script)))
+            if let ParserError::User { error } = err {
+                format!("{}", error.clone().with_default_script(Some(script)))
+            } else {
+                format!("{}", err.clone().map_location(|pos| format_location(pos, script)))
+            }
         } else {
             format!("{}", err)
         };
@@ -106,6 +67,18 @@ fn display_parsing_recovery(
     Ok(())
 }
 
+fn display_compiling_recovery(errors: &[AscesisError], f: &mut fmt::Formatter) -> fmt::Result {
+    for (num, err) in errors.iter().enumerate() {
+        if num > 0 {
+            write!(f, "\nerror: {}", err)?;
+        } else {
+            write!(f, "{}", err)?;
+        }
+    }
+
+    Ok(())
+}
+
 fn display_lexing_failure(
     token: &str,
     span: &logos::Span,
@@ -120,15 +93,32 @@ pub enum AscesisErrorKind {
     ParsingRecovery(Vec<ParserError>),
     LexingFailure(String, logos::Span),
     ParsingFailure,
+    CompilingRecovery(Vec<AscesisError>),
     AxiomUnknown(String),
     RootUnset,
-    RootMissing(String),
-    RootRedefined(String),
+    RootMissing(String, Option<String>),
+    RootRedefined(String, Span),
     RootBlockMismatch,
     RootBlockMissing,
     RootUnresolvable,
     ScriptUncompiled,
     UnexpectedDependency(String),
+    TemplateMissing(String, Option<String>),
+    TemplateArgRedefined(String),
+    TemplateArityMismatch(String, usize, usize),
+    TemplateArgTypeMismatch(String, String, ArgType),
+    UnboundSizeArg(String),
+    HybridArgMisuse(String, String),
+    TemplateRedefined(String, Span),
+    ArgTypeUnknown(String),
+    SizeArgUndeclared(String),
+    StructureUndefined(String, Option<String>, Span),
+    StructureCycle(Vec<String>, Span, Span),
+    StructureRedefined(String, Span),
+    ImportFailure(String, String, Span),
+    ImportCycle(Vec<String>, Span),
+    NamespaceConflict(String, Vec<String>, Span),
+    StructureAmbiguous(String, Vec<String>, Span),
     InvalidAST,
     FatLeak,
     MissingPropSelector,
@@ -151,17 +141,62 @@ impl fmt::Display for AscesisErrorKind {
 
         match self {
             ParsingRecovery(ref errors) => display_parsing_recovery(errors, None, f),
-            LexingFailure(token, span) => write!(f, "Invalid token \"{}\" at {:?}", token, span),
+            LexingFailure(token, span) => {
+                write!(f, "Invalid token \"{}\" at {}", token, Span::new(span.start, span.end))
+            }
             ParsingFailure => write!(f, "Recovering from ascesis parsing errors"),
+            CompilingRecovery(ref errors) => display_compiling_recovery(errors, f),
             AxiomUnknown(symbol) => write!(f, "Unknown axiom '{}'", symbol),
             RootUnset => write!(f, "Undeclared root structure"),
-            RootMissing(name) => write!(f, "Missing root structure '{}'", name),
-            RootRedefined(name) => write!(f, "Redefined root structure '{}'", name),
+            RootMissing(name, _) => write!(f, "Missing root structure '{}'", name),
+            RootRedefined(name, _) => write!(f, "Redefined root structure '{}'", name),
             RootBlockMismatch => write!(f, "Root block mismatch"),
             RootBlockMissing => write!(f, "Root block missing"),
             RootUnresolvable => write!(f, "Root contains instances without known definitions"),
             ScriptUncompiled => write!(f, "Script uncompiled"),
             UnexpectedDependency(name) => write!(f, "Unexpected uncompiled dependency '{}'", name),
+            TemplateMissing(name, _) => write!(f, "Missing template '{}'", name),
+            TemplateArgRedefined(name) => write!(f, "Redefined template argument '{}'", name),
+            TemplateArityMismatch(name, expected, actual) => write!(
+                f,
+                "Template '{}' takes {} argument(s), but {} were given",
+                name, expected, actual
+            ),
+            TemplateArgTypeMismatch(name, arg, arg_type) => {
+                write!(f, "Argument '{}' of template '{}' is not a {}", arg, name, arg_type)
+            }
+            UnboundSizeArg(arg) => write!(f, "Capacity given by unbound argument '{}'", arg),
+            HybridArgMisuse(name, arg) => write!(
+                f,
+                "Argument '{}' of template '{}' is bound to a structure, but used as a dot",
+                arg, name
+            ),
+            TemplateRedefined(name, _) => write!(f, "Redefined template '{}'", name),
+            ArgTypeUnknown(name) => write!(f, "Unknown argument type '{}'", name),
+            SizeArgUndeclared(name) => write!(
+                f,
+                "Capacity given by '{}', which isn't a Size argument of an enclosing template",
+                name
+            ),
+            StructureUndefined(name, ..) => write!(f, "Undefined structure '{}'", name),
+            StructureRedefined(name, _) => write!(f, "Redefined structure '{}'", name),
+            StructureCycle(names, ..) => {
+                write!(f, "Cyclic dependency of structures {}", names.join(" -> "))
+            }
+            ImportFailure(path, err, _) => write!(f, "Failed to import \"{}\": {}", path, err),
+            ImportCycle(paths, _) => write!(f, "Cyclic import {}", paths.join(" -> ")),
+            NamespaceConflict(namespace, paths, _) => write!(
+                f,
+                "Namespace '{}' is taken by both \"{}\"",
+                namespace,
+                paths.join("\" and \"")
+            ),
+            StructureAmbiguous(name, candidates, _) => write!(
+                f,
+                "Ambiguous name '{}', which may refer to any of {}",
+                name,
+                candidates.join(", ")
+            ),
             InvalidAST => write!(f, "Invalid AST"),
             FatLeak => write!(f, "Fat arrow rule leaked through FIT transformation"),
             MissingPropSelector => write!(f, "Property block without selector"),
@@ -185,8 +220,82 @@ impl fmt::Display for AscesisErrorKind {
 }
 
 impl AscesisErrorKind {
+    /// Returns the code identifying this kind of error in
+    /// [`Diagnostic`]s.
+    ///
+    /// Codes are stable: a code is never reassigned, even if the kind
+    /// of error it identified is removed.  The second digit groups
+    /// codes by subject: syntax (`E01xx`), structures and files
+    /// (`E02xx`), templates (`E03xx`), property blocks (`E04xx`) and
+    /// literals (`E05xx`).
+    pub fn get_code(&self) -> &'static str {
+        use AscesisErrorKind::*;
+
+        match self {
+            ParsingRecovery(_) => "E0100",
+            LexingFailure(..) => "E0105",
+            ParsingFailure => "E0106",
+            CompilingRecovery(_) => "E0107",
+            AxiomUnknown(_) => "E0201",
+            RootUnset => "E0202",
+            RootMissing(..) => "E0203",
+            RootRedefined(..) => "E0204",
+            RootBlockMismatch => "E0205",
+            RootBlockMissing => "E0206",
+            RootUnresolvable => "E0207",
+            ScriptUncompiled => "E0208",
+            UnexpectedDependency(_) => "E0209",
+            StructureUndefined(..) => "E0210",
+            StructureCycle(..) => "E0211",
+            StructureAmbiguous(..) => "E0212",
+            ImportFailure(..) => "E0213",
+            ImportCycle(..) => "E0214",
+            InvalidAST => "E0215",
+            FatLeak => "E0216",
+            NamespaceConflict(..) => "E0217",
+            StructureRedefined(..) => "E0218",
+            TemplateMissing(..) => "E0301",
+            TemplateArgRedefined(_) => "E0302",
+            TemplateArityMismatch(..) => "E0303",
+            TemplateArgTypeMismatch(..) => "E0304",
+            UnboundSizeArg(_) => "E0305",
+            HybridArgMisuse(..) => "E0306",
+            ArgTypeUnknown(_) => "E0307",
+            SizeArgUndeclared(_) => "E0308",
+            TemplateRedefined(..) => "E0309",
+            MissingPropSelector => "E0401",
+            InvalidPropSelector(_) => "E0402",
+            InvalidPropType(..) => "E0403",
+            InvalidPropValue(..) => "E0404",
+            InvalidPropValueType(_) => "E0405",
+            BlockSelectorMismatch(..) => "E0406",
+            SizeLiteralOverflow => "E0501",
+            ExpectedSizeLiteral => "E0502",
+            ExpectedNameLiteral => "E0503",
+            ParseIntFailure(_) => "E0504",
+            EnquoteFailure(_) => "E0505",
+            NotADotList => "E0506",
+        }
+    }
+
     pub fn with_script<S: AsRef<str>>(self, script: S) -> AscesisError {
-        AscesisError { script: Some(script.as_ref().to_owned()), kind: self }
+        AscesisError { script: Some(script.as_ref().to_owned()), span: None, kind: self }
+    }
+
+    /// Returns the span carried by this kind of error, if any.
+    pub fn get_span(&self) -> Option<Span> {
+        use AscesisErrorKind::*;
+
+        match self {
+            LexingFailure(_, span) => Some(Span::new(span.start, span.end)),
+            StructureUndefined(_, _, span)
+            | StructureCycle(_, span, _)
+            | ImportFailure(_, _, span)
+            | ImportCycle(_, span)
+            | NamespaceConflict(_, _, span)
+            | StructureAmbiguous(_, _, span) => Some(*span),
+            _ => None,
+        }
     }
 }
 
@@ -219,13 +328,256 @@ impl From<ParseIntError> for AscesisErrorKind {
 #[derive(Clone, Debug)]
 pub struct AscesisError {
     script: Option<String>,
+    span:   Option<Span>,
     kind:   AscesisErrorKind,
 }
 
+impl AscesisError {
+    /// Combines independent errors found in a single compilation pass.
+    /// A single error is returned as is.
+    pub(crate) fn from_errors(mut errors: Vec<AscesisError>) -> Self {
+        if errors.len() == 1 {
+            errors.pop().unwrap()
+        } else {
+            AscesisErrorKind::CompilingRecovery(errors).into()
+        }
+    }
+
+    /// Attaches the span of the source text this error was caused by,
+    /// unless a span is already attached, or carried by the kind of
+    /// this error.  Since errors are raised bottom-up, this keeps the
+    /// most precise span known.
+    pub fn with_span(mut self, span: Span) -> Self {
+        if self.get_span().is_none() && !span.is_empty() {
+            self.span = Some(span);
+        }
+        self
+    }
+
+    /// Attaches the script this error was found in, unless a script is
+    /// already attached.
+    pub(crate) fn with_default_script(mut self, script: Option<&str>) -> Self {
+        if self.script.is_none() {
+            self.script = script.map(ToOwned::to_owned);
+        }
+        self
+    }
+
+    #[inline]
+    pub fn get_kind(&self) -> &AscesisErrorKind {
+        &self.kind
+    }
+
+    /// Returns the span of the source text this error was caused by,
+    /// if known.
+    pub fn get_span(&self) -> Option<Span> {
+        self.span.or_else(|| self.kind.get_span())
+    }
+
+    #[inline]
+    pub fn get_script(&self) -> Option<&str> {
+        self.script.as_deref()
+    }
+}
+
+impl AscesisError {
+    /// Describes this error as a sequence of [`Diagnostic`]s, one for
+    /// each syntax or compilation error recovered from, or a single
+    /// one otherwise.
+    pub fn to_diagnostics(&self) -> Vec<Diagnostic> {
+        use AscesisErrorKind::*;
+
+        match self.kind {
+            ParsingRecovery(ref errors) => {
+                let script = self.script.as_deref();
+
+                return errors.iter().map(|err| parser_error_to_diagnostic(err, script)).collect()
+            }
+            CompilingRecovery(ref errors) => {
+                return errors.iter().flat_map(|err| err.to_diagnostics()).collect()
+            }
+            _ => {}
+        }
+
+        let mut diagnostic =
+            Diagnostic::error(self.kind.to_string()).with_code(self.kind.get_code());
+
+        if let Some(span) = self.get_span() {
+            let label = match self.kind {
+                StructureUndefined(..) => Some("not defined".to_owned()),
+                StructureCycle(..) => Some("closes the cycle".to_owned()),
+                RootRedefined(..) | StructureRedefined(..) | TemplateRedefined(..) => {
+                    Some("redefined here".to_owned())
+                }
+                _ => None,
+            };
+
+            diagnostic = diagnostic.with_primary_label(span, label);
+        }
+
+        match self.kind {
+            RootRedefined(_, first_span)
+            | StructureRedefined(_, first_span)
+            | TemplateRedefined(_, first_span)
+                if !first_span.is_empty() =>
+            {
+                diagnostic = diagnostic.with_secondary_label(first_span, "first defined here");
+            }
+            StructureCycle(ref names, _, def_span) if !def_span.is_empty() => {
+                diagnostic = diagnostic
+                    .with_secondary_label(def_span, format!("'{}' is defined here", names[0]));
+            }
+            StructureAmbiguous(ref name, ref candidates, span) => {
+                if let Some(rest) = self.get_text_after(name, span) {
+                    for candidate in candidates {
+                        diagnostic = diagnostic.with_suggestion(
+                            span,
+                            format!("{}{}", candidate, rest),
+                            "use a qualified name",
+                        );
+                    }
+                }
+            }
+            StructureUndefined(ref name, Some(ref similar), span) => {
+                if let Some(rest) = self.get_text_after(name, span) {
+                    diagnostic = diagnostic.with_suggestion(
+                        span,
+                        format!("{}{}", similar, rest),
+                        "a structure with a similar name exists",
+                    );
+                }
+            }
+            TemplateMissing(ref name, Some(ref similar)) => {
+                if let Some(span) = self.span {
+                    if let Some(rest) = self.get_text_after(name, span) {
+                        diagnostic = diagnostic.with_suggestion(
+                            span,
+                            format!("{}{}", similar, rest),
+                            "a template with a similar name exists",
+                        );
+                    }
+                }
+            }
+            RootMissing(_, Some(ref similar)) => {
+                diagnostic = diagnostic.with_note(format!("did you mean '{}'?", similar));
+            }
+            TemplateArityMismatch(ref name, expected, _) => {
+                diagnostic = diagnostic.with_note(format!(
+                    "template '{}' is declared with {} argument(s)",
+                    name, expected
+                ));
+            }
+            _ => {}
+        }
+
+        vec![diagnostic]
+    }
+
+    /// Returns the source text of `span` following `name`, if the text
+    /// starts with `name`.
+    fn get_text_after(&self, name: &str, span: Span) -> Option<&str> {
+        self.script
+            .as_deref()
+            .and_then(|script| script.get(span.start()..span.end()))
+            .and_then(|text| text.strip_prefix(name))
+    }
+}
+
+fn parser_error_to_diagnostic(err: &ParserError, script: Option<&str>) -> Diagnostic {
+    use lalrpop_util::ParseError::*;
+
+    match err {
+        InvalidToken { location } => Diagnostic::error("Invalid token")
+            .with_code("E0101")
+            .with_primary_label(Span::new(*location, *location + 1), None),
+        UnrecognizedEOF { location, expected } => {
+            let span = Span::new(*location, *location);
+            let mut diagnostic = with_expected(
+                Diagnostic::error("Unexpected end of file")
+                    .with_code("E0102")
+                    .with_primary_label(span, None),
+                expected,
+            );
+
+            let closer = script.and_then(|script| script.get(..*location)).and_then(find_unclosed);
+
+            if let Some(closer) = closer {
+                diagnostic =
+                    diagnostic.with_suggestion(span, closer, "insert the missing delimiter");
+            }
+
+            diagnostic
+        }
+        UnrecognizedToken { token: (start, token, end), expected } => {
+            let mut diagnostic = with_expected(
+                Diagnostic::error(format!("Unexpected token `{}`", token))
+                    .with_code("E0103")
+                    .with_primary_label(
+                        Span::new(*start, *end),
+                        Some("unexpected token".to_owned()),
+                    ),
+                expected,
+            );
+
+            // A single literal token expected is most likely missing.
+            if let [token] = expected.as_slice() {
+                if let Some(token) = token.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+                    diagnostic = diagnostic.with_suggestion(
+                        Span::new(*start, *start),
+                        token,
+                        "insert the missing token",
+                    );
+                }
+            }
+
+            diagnostic
+        }
+        ExtraToken { token: (start, token, end) } => {
+            Diagnostic::error(format!("Extra token `{}`", token))
+                .with_code("E0104")
+                .with_primary_label(Span::new(*start, *end), None)
+                .with_suggestion(Span::new(*start, *end), "", "remove the token")
+        }
+        User { error } => error
+            .to_diagnostics()
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| Diagnostic::error(error.to_string())),
+    }
+}
+
+fn with_expected(diagnostic: Diagnostic, expected: &[String]) -> Diagnostic {
+    if expected.is_empty() {
+        diagnostic
+    } else {
+        diagnostic.with_note(format!("expected one of {}", expected.join(", ")))
+    }
+}
+
+/// Returns the closing delimiter matching the innermost delimiter left
+/// open in `script`, if any.
+fn find_unclosed(script: &str) -> Option<&'static str> {
+    let mut stack = Vec::new();
+
+    for (_, token, _) in Lexer::new(script).filter_map(Result::ok) {
+        match token {
+            Token::OpenCurly => stack.push("}"),
+            Token::OpenParen => stack.push(")"),
+            Token::OpenBracket => stack.push("]"),
+            Token::CloseCurly | Token::CloseParen | Token::CloseBracket => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    stack.pop()
+}
+
 impl From<AscesisErrorKind> for AscesisError {
     #[inline]
     fn from(kind: AscesisErrorKind) -> Self {
-        AscesisError { script: None, kind }
+        AscesisError { script: None, span: None, kind }
     }
 }
 
@@ -239,7 +591,21 @@ impl fmt::Display for AscesisError {
                 LexingFailure(ref token, ref span) => {
                     display_lexing_failure(token.as_str(), span, script, f)
                 }
-                ref kind => kind.fmt(f),
+                StructureUndefined(_, _, span)
+                | StructureCycle(_, span, _)
+                | ImportFailure(_, _, span)
+                | ImportCycle(_, span)
+                | NamespaceConflict(_, _, span)
+                | StructureAmbiguous(_, _, span) => {
+                    write!(f, "{} at {}", self.kind, format_span(&span.into(), script))
+                }
+                ref kind => {
+                    if let Some(span) = self.span {
+                        write!(f, "{} at {}", kind, format_span(&span.into(), script))
+                    } else {
+                        kind.fmt(f)
+                    }
+                }
             }
         } else {
             self.kind.fmt(f)
@@ -248,3 +614,137 @@ impl fmt::Display for AscesisError {
 }
 
 impl Error for AscesisError {}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AscesisWarningKind {
+    SumIdempotency(Vec<DotName>),
+    ProductIdempotency(DotName),
+    BlockIgnored(String),
+    DotUnused(String, Option<String>),
+    PropKeyUnknown(PropSelector, String, Option<String>),
+    LinkIncoherent(DotName, DotName, Polarity),
+    RenameConflict(String, DotName, DotName, DotName),
+}
+
+impl fmt::Display for AscesisWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use AscesisWarningKind::*;
+
+        match self {
+            SumIdempotency(dots) => {
+                let dots: Vec<_> = dots.iter().map(|dot| dot.as_ref()).collect();
+
+                write!(f, "Monomial '{}' added more than once", dots.join(" "))
+            }
+            ProductIdempotency(dot) => write!(f, "Dot '{}' multiplied by itself", dot),
+            BlockIgnored(reason) => write!(f, "Block ignored: {}", reason),
+            DotUnused(dot, _) => write!(f, "Dot '{}' isn't used in any structure", dot),
+            PropKeyUnknown(selector, key, _) => {
+                write!(f, "Unknown {} property '{}'", selector, key)
+            }
+            LinkIncoherent(tx_dot, rx_dot, Polarity::Tx) => write!(
+                f,
+                "Effect-only link ({} >? {}): no cause of dot '{}' involves dot '{}'",
+                tx_dot, rx_dot, rx_dot, tx_dot
+            ),
+            LinkIncoherent(tx_dot, rx_dot, Polarity::Rx) => write!(
+                f,
+                "Cause-only link ({} ?> {}): no effect of dot '{}' involves dot '{}'",
+                tx_dot, rx_dot, tx_dot, rx_dot
+            ),
+            RenameConflict(what, dropped, kept, new_name) => write!(
+                f,
+                "Dots '{}' and '{}' are both renamed to '{}', dropping the {} of '{}'",
+                dropped, kept, new_name, what, dropped
+            ),
+        }
+    }
+}
+
+impl AscesisWarningKind {
+    /// Returns the code identifying this kind of warning in
+    /// [`Diagnostic`]s.  Codes are stable, see
+    /// [`AscesisErrorKind::get_code()`].
+    pub fn get_code(&self) -> &'static str {
+        use AscesisWarningKind::*;
+
+        match self {
+            SumIdempotency(_) => "W0001",
+            ProductIdempotency(_) => "W0002",
+            BlockIgnored(_) => "W0003",
+            DotUnused(..) => "W0004",
+            PropKeyUnknown(..) => "W0005",
+            LinkIncoherent(..) => "W0006",
+            RenameConflict(..) => "W0007",
+        }
+    }
+}
+
+/// A problem which doesn't prevent compilation of a script, e.g. an
+/// expression simplified by applying idempotency laws.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AscesisWarning {
+    kind: AscesisWarningKind,
+    span: Span,
+}
+
+impl AscesisWarning {
+    /// Attaches the span of the source text this warning was caused
+    /// by, unless a span is already attached.
+    pub(crate) fn with_default_span(mut self, span: Span) -> Self {
+        if self.span.is_empty() {
+            self.span = span;
+        }
+        self
+    }
+
+    #[inline]
+    pub fn get_kind(&self) -> &AscesisWarningKind {
+        &self.kind
+    }
+
+    /// Returns the span of the source text this warning was caused by,
+    /// if known.
+    pub fn get_span(&self) -> Option<Span> {
+        if self.span.is_empty() {
+            None
+        } else {
+            Some(self.span)
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        use AscesisWarningKind::*;
+
+        let diagnostic = Diagnostic::warning(self.kind.to_string()).with_code(self.kind.get_code());
+
+        if let Some(span) = self.get_span() {
+            let diagnostic = diagnostic.with_primary_label(span, None);
+
+            match self.kind {
+                DotUnused(_, Some(ref similar)) => {
+                    diagnostic.with_suggestion(span, similar, "a dot with a similar name is used")
+                }
+                PropKeyUnknown(_, _, Some(ref similar)) => {
+                    diagnostic.with_suggestion(span, similar, "a known property has a similar name")
+                }
+                _ => diagnostic,
+            }
+        } else {
+            diagnostic
+        }
+    }
+}
+
+impl From<AscesisWarningKind> for AscesisWarning {
+    #[inline]
+    fn from(kind: AscesisWarningKind) -> Self {
+        AscesisWarning { kind, span: Span::default() }
+    }
+}
+
+impl fmt::Display for AscesisWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
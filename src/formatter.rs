@@ -0,0 +1,764 @@
+use std::{borrow::Cow, error::Error};
+use crate::{CesFile, Lexer, Token, AscesisError};
+
+/// Layout options of the [`Formatter`].
+///
+/// Defaults follow the conventions of this crate's own
+/// `rustfmt.toml`: lines are at most 100 characters wide, and each
+/// level of indentation is 4 spaces.
+#[derive(Clone, Debug)]
+pub struct FormatterConfig {
+    max_width:  usize,
+    tab_spaces: usize,
+}
+
+impl Default for FormatterConfig {
+    fn default() -> Self {
+        FormatterConfig { max_width: 100, tab_spaces: 4 }
+    }
+}
+
+impl FormatterConfig {
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_max_width(mut self, max_width: usize) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    pub fn with_tab_spaces(mut self, tab_spaces: usize) -> Self {
+        self.tab_spaces = tab_spaces;
+        self
+    }
+
+    #[inline]
+    pub fn get_max_width(&self) -> usize {
+        self.max_width
+    }
+
+    #[inline]
+    pub fn get_tab_spaces(&self) -> usize {
+        self.tab_spaces
+    }
+}
+
+/// Source formatter of _Ascesis_ scripts.
+///
+/// Unlike the `Display` implementation of [`CesFile`], the formatter
+/// works on the token stream, so that it keeps `//` comments and
+/// layout choices which don't affect the result of parsing, e.g. the
+/// order of fields in context blocks.  It normalises spacing between
+/// tokens, indentation, and placement of braces.  A bracketed group
+/// is put on a single line if it fits, otherwise it is broken into
+/// one line per field, ending with a comma, or per term of a rule
+/// expression.  Top-level blocks are separated with a blank line,
+/// except for consecutive `use` directives.
+#[derive(Clone, Default, Debug)]
+pub struct Formatter {
+    config: FormatterConfig,
+}
+
+impl Formatter {
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_config(mut self, config: FormatterConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    #[inline]
+    pub fn get_config(&self) -> &FormatterConfig {
+        &self.config
+    }
+
+    /// Returns formatted `script`.
+    ///
+    /// The `script` has to be syntactically valid, otherwise parsing
+    /// error is returned.
+    pub fn format<S: AsRef<str>>(&self, script: S) -> Result<String, Box<dyn Error>> {
+        let script = script.as_ref();
+
+        // A comment token includes the terminating newline, which may
+        // be missing at the end of a script.
+        let script = if script.ends_with('\n') {
+            Cow::Borrowed(script)
+        } else {
+            Cow::Owned(format!("{}\n", script))
+        };
+
+        CesFile::from_script(script.as_ref())?;
+
+        let nodes = build_tree(script.as_ref())?;
+        let mut printer = Printer::new(&self.config);
+
+        printer.fmt_top_level(&nodes);
+
+        Ok(printer.finish())
+    }
+
+    /// Returns `true` if `script` is already formatted, i.e. if
+    /// formatting would leave it unchanged.
+    pub fn check<S: AsRef<str>>(&self, script: S) -> Result<bool, Box<dyn Error>> {
+        let script = script.as_ref();
+
+        Ok(self.format(script)? == script)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum GroupKind {
+    /// Comma-separated fields of a context block, an array, or a
+    /// list of arguments.
+    Fields { trailing_comma: bool },
+    /// Body of a structure definition, or a braced rule expression.
+    Rex,
+}
+
+#[derive(Debug)]
+struct Group<'a> {
+    open:        Token<'a>,
+    close:       Token<'a>,
+    kind:        GroupKind,
+    is_attached: bool,
+    nodes:       Vec<Node<'a>>,
+}
+
+impl<'a> Group<'a> {
+    /// Returns `true` if this group contains context blocks, which
+    /// are always put on separate lines.
+    fn has_blocks(&self) -> bool {
+        self.kind == GroupKind::Rex
+            && self
+                .nodes
+                .iter()
+                .any(|node| matches!(node.item, Item::Token(token) if is_block_keyword(token)))
+    }
+}
+
+#[derive(Debug)]
+enum Item<'a> {
+    Token(Token<'a>),
+    Group(Group<'a>),
+    Comment(&'a str),
+    TrailingComment(&'a str),
+    DocComment(&'a str),
+}
+
+impl<'a> Item<'a> {
+    fn is_comment(&self) -> bool {
+        matches!(self, Item::Comment(_) | Item::DocComment(_))
+    }
+}
+
+#[derive(Debug)]
+struct Node<'a> {
+    item:      Item<'a>,
+    /// Set if the node is preceded with an empty line.
+    is_spaced: bool,
+}
+
+fn is_block_keyword(token: Token) -> bool {
+    use Token::*;
+
+    matches!(token, Vis | Sat | Caps | Unbounded | Weights | Inhibit | Activate | Drop)
+}
+
+fn is_arrow(token: Token) -> bool {
+    use Token::*;
+
+    matches!(token, ThinArrow | ThinBackArrow | FatArrow | FatBackArrow | FatTwowayArrow)
+}
+
+fn closing_of(open: Token) -> Token {
+    match open {
+        Token::OpenCurly => Token::CloseCurly,
+        Token::OpenParen => Token::CloseParen,
+        _ => Token::CloseBracket,
+    }
+}
+
+/// Returns the last two tokens of `nodes`, skipping comments.
+fn last_tokens<'a>(nodes: &[Node<'a>]) -> (Option<Token<'a>>, Option<Token<'a>>) {
+    let mut tokens = nodes.iter().rev().filter_map(|node| match node.item {
+        Item::Token(token) => Some(Some(token)),
+        Item::Group(_) => Some(None),
+        _ => None,
+    });
+
+    (tokens.next().flatten(), tokens.next().flatten())
+}
+
+fn new_group<'a>(open: Token<'a>, nodes: Vec<Node<'a>>, siblings: &[Node<'a>]) -> Group<'a> {
+    let (prev, prev_prev) = last_tokens(siblings);
+
+    let (kind, is_attached) = match open {
+        Token::OpenCurly => match prev {
+            Some(Token::Colon) => (GroupKind::Fields { trailing_comma: true }, false),
+            Some(Token::Unbounded) => (GroupKind::Fields { trailing_comma: false }, false),
+            Some(token) if is_block_keyword(token) => {
+                (GroupKind::Fields { trailing_comma: true }, false)
+            }
+            _ => (GroupKind::Rex, false),
+        },
+        Token::OpenParen => {
+            // Arguments follow a structure name, otherwise parens
+            // group a polynomial.
+            let is_args = match prev {
+                Some(Token::Bang) => true,
+                Some(Token::Identifier(_)) => nodes.is_empty() || prev_prev == Some(Token::Ces),
+                _ => false,
+            };
+
+            (GroupKind::Fields { trailing_comma: is_args }, is_args)
+        }
+        _ => (GroupKind::Fields { trailing_comma: true }, false),
+    };
+
+    Group { open, close: closing_of(open), kind, is_attached, nodes }
+}
+
+fn build_tree(script: &str) -> Result<Vec<Node<'_>>, AscesisError> {
+    let mut stack = Vec::new();
+    let mut nodes = Vec::new();
+    let mut prev_end = None;
+
+    for result in Lexer::with_comments(script) {
+        let (start, token, end) = result?;
+        let num_newlines = script[prev_end.unwrap_or(0)..start].matches('\n').count();
+        let is_spaced = num_newlines > 1;
+
+        let item = match token {
+            Token::Comment(text) => {
+                if prev_end.is_some() && num_newlines == 0 {
+                    Item::TrailingComment(text)
+                } else {
+                    Item::Comment(text)
+                }
+            }
+            Token::DocComment(text) => Item::DocComment(text),
+            Token::OpenCurly | Token::OpenParen | Token::OpenBracket => {
+                stack.push((token, is_spaced, std::mem::take(&mut nodes)));
+                prev_end = Some(end);
+                continue
+            }
+            Token::CloseCurly | Token::CloseParen | Token::CloseBracket => {
+                if let Some((open, is_spaced, siblings)) = stack.pop() {
+                    let group = new_group(open, std::mem::replace(&mut nodes, siblings), &nodes);
+
+                    nodes.push(Node { item: Item::Group(group), is_spaced });
+                    prev_end = Some(end);
+                    continue
+                } else {
+                    Item::Token(token)
+                }
+            }
+            _ => Item::Token(token),
+        };
+
+        // Comments end with a newline, which belongs to the gap
+        // before the next token.
+        prev_end = Some(if item.is_comment() || matches!(item, Item::TrailingComment(_)) {
+            end - 1
+        } else {
+            end
+        });
+
+        nodes.push(Node { item, is_spaced });
+    }
+
+    Ok(nodes)
+}
+
+fn needs_space(prev: Token, next: Token, is_attached: bool) -> bool {
+    use Token::*;
+
+    match (prev, next) {
+        (OpenParen, _) | (OpenBracket, _) | (PathSep, _) | (Bang, _) => false,
+        (_, CloseParen) | (_, CloseBracket) | (_, Comma) | (_, Colon) | (_, Semicolon) => false,
+        (_, PathSep) | (_, Bang) => false,
+        (OpenCurly, CloseCurly) => false,
+        (_, OpenParen) => !is_attached,
+        _ => true,
+    }
+}
+
+/// Appends `nodes` to `out` as a single line, unless they contain
+/// comments.
+fn fmt_flat<'a>(nodes: &[&Node<'a>], out: &mut String, last: &mut Option<Token<'a>>) -> Option<()> {
+    for node in nodes {
+        match &node.item {
+            Item::Token(token) => push_flat(*token, false, out, last),
+            Item::Group(group) => fmt_flat_group(group, out, last)?,
+            _ => return None,
+        }
+    }
+
+    Some(())
+}
+
+/// Appends `group` to `out` as a single line, dropping the trailing
+/// comma, unless the group contains comments.
+fn fmt_flat_group<'a>(
+    group: &Group<'a>,
+    out: &mut String,
+    last: &mut Option<Token<'a>>,
+) -> Option<()> {
+    let mut nodes: Vec<_> = group.nodes.iter().collect();
+
+    if matches!(group.kind, GroupKind::Fields { .. }) {
+        if let Some(Node { item: Item::Token(Token::Comma), .. }) = nodes.last() {
+            nodes.pop();
+        }
+    }
+
+    push_flat(group.open, group.is_attached, out, last);
+    fmt_flat(&nodes, out, last)?;
+    push_flat(group.close, false, out, last);
+
+    Some(())
+}
+
+fn push_flat<'a>(
+    token: Token<'a>,
+    is_attached: bool,
+    out: &mut String,
+    last: &mut Option<Token<'a>>,
+) {
+    if let Some(prev) = *last {
+        if needs_space(prev, token, is_attached) {
+            out.push(' ');
+        }
+    }
+
+    out.push_str(&String::from(token));
+    *last = Some(token);
+}
+
+struct Printer<'c, 'a> {
+    config:       &'c FormatterConfig,
+    lines:        Vec<String>,
+    line:         String,
+    indent:       usize,
+    last:         Option<Token<'a>>,
+    last_keyword: Option<Token<'a>>,
+}
+
+impl<'c, 'a> Printer<'c, 'a> {
+    fn new(config: &'c FormatterConfig) -> Self {
+        Printer {
+            config,
+            lines: Vec::new(),
+            line: String::new(),
+            indent: 0,
+            last: None,
+            last_keyword: None,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.newline();
+
+        let mut result = self.lines.join("\n");
+
+        result.push('\n');
+        result
+    }
+
+    fn width(&self) -> usize {
+        self.line.chars().count()
+    }
+
+    fn newline(&mut self) {
+        if !self.line.is_empty() {
+            self.lines.push(std::mem::take(&mut self.line));
+        }
+        self.last = None;
+    }
+
+    fn blank_line(&mut self) {
+        self.newline();
+
+        if matches!(self.lines.last(), Some(line) if !line.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    fn push_str(&mut self, text: &str, with_space: bool) {
+        if self.line.is_empty() {
+            self.line = " ".repeat(self.indent * self.config.tab_spaces);
+        } else if with_space {
+            self.line.push(' ');
+        }
+
+        self.line.push_str(text);
+    }
+
+    fn push_token(&mut self, token: Token<'a>, is_attached: bool) {
+        let with_space = matches!(self.last, Some(prev) if needs_space(prev, token, is_attached));
+
+        self.push_str(&String::from(token), with_space);
+        self.last = Some(token);
+    }
+
+    fn push_comment(&mut self, item: &Item) {
+        match item {
+            Item::Comment(text) => {
+                self.newline();
+                self.push_str(text, false);
+            }
+            Item::DocComment(text) => {
+                self.newline();
+
+                if text.is_empty() {
+                    self.push_str("///", false);
+                } else {
+                    self.push_str(&format!("/// {}", text), false);
+                }
+            }
+            Item::TrailingComment(text) => self.push_str(text, true),
+            _ => return,
+        }
+
+        self.newline();
+    }
+
+    /// Returns `true` if `text` fits in the current line, after a
+    /// space, if needed.
+    fn fits(&self, text: &str) -> bool {
+        let width = if self.line.is_empty() {
+            self.indent * self.config.tab_spaces
+        } else {
+            self.width() + 1
+        };
+
+        width + text.chars().count() <= self.config.max_width
+    }
+
+    /// Splits `nodes` into runs of tokens and groups, starting a new
+    /// run at each standalone comment, and before each token for
+    /// which `is_start` returns `true`.
+    fn split_runs<'n, F>(nodes: &'n [Node<'a>], is_start: F) -> Vec<Vec<&'n Node<'a>>>
+    where
+        F: Fn(Token) -> bool,
+    {
+        let mut runs: Vec<Vec<&Node>> = Vec::new();
+        let mut run = Vec::new();
+
+        for node in nodes {
+            let is_new = match node.item {
+                Item::Token(token) => is_start(token),
+                Item::Comment(_) | Item::DocComment(_) => true,
+                _ => false,
+            };
+
+            if is_new && !run.is_empty() {
+                runs.push(std::mem::take(&mut run));
+            }
+
+            run.push(node);
+
+            if node.item.is_comment() {
+                runs.push(std::mem::take(&mut run));
+            }
+        }
+
+        if !run.is_empty() {
+            runs.push(run);
+        }
+
+        runs
+    }
+
+    fn fmt_top_level(&mut self, nodes: &[Node<'a>]) {
+        let runs = Self::split_runs(nodes, |token| {
+            matches!(token, Token::Ces | Token::Use) || is_block_keyword(token)
+        });
+        let mut after_comment = false;
+
+        for run in runs {
+            let head = run[0];
+
+            if !self.lines.is_empty() {
+                let is_use = matches!(head.item, Item::Token(Token::Use));
+
+                if head.is_spaced
+                    || !after_comment && !(is_use && self.last_keyword == Some(Token::Use))
+                {
+                    self.blank_line();
+                }
+            }
+
+            after_comment = head.item.is_comment();
+
+            if after_comment {
+                self.push_comment(&head.item);
+            } else {
+                if let Item::Token(token) = head.item {
+                    self.last_keyword = Some(token);
+                }
+                self.newline();
+                self.fmt_run(&run);
+                self.newline();
+            }
+        }
+    }
+
+    /// Formats the contents of a broken group of kind
+    /// [`GroupKind::Rex`]: a rule expression followed by context
+    /// blocks.
+    fn fmt_rex_contents(&mut self, nodes: &[Node<'a>]) {
+        for (num, run) in Self::split_runs(nodes, is_block_keyword).into_iter().enumerate() {
+            let head = run[0];
+
+            if num > 0 && head.is_spaced {
+                self.blank_line();
+            }
+
+            if head.item.is_comment() {
+                self.push_comment(&head.item);
+            } else if matches!(head.item, Item::Token(token) if is_block_keyword(token)) {
+                self.newline();
+                self.fmt_run(&run);
+            } else {
+                self.fmt_rex(&run);
+            }
+        }
+    }
+
+    /// Formats a rule expression on a single line if it fits,
+    /// otherwise one term per line.
+    fn fmt_rex(&mut self, run: &[&Node<'a>]) {
+        self.newline();
+
+        let mut flat = String::new();
+
+        if fmt_flat(run, &mut flat, &mut None).is_some() && self.fits(&flat) {
+            self.fmt_run(run);
+            return
+        }
+
+        // Arrow rules aren't split.
+        if run.iter().any(|node| matches!(node.item, Item::Token(token) if is_arrow(token))) {
+            self.fmt_run(run);
+            return
+        }
+
+        let mut term = Vec::new();
+        let mut plus = None;
+
+        for &node in run {
+            match node.item {
+                Item::Token(Token::Add) if term.is_empty() => plus = Some(node),
+                Item::TrailingComment(_) => {
+                    self.fmt_term(plus.take(), &mut term);
+                    self.push_comment(&node.item);
+                }
+                Item::Group(_) => {
+                    term.push(node);
+                    self.fmt_term(plus.take(), &mut term);
+                }
+                _ => term.push(node),
+            }
+        }
+
+        self.fmt_term(plus, &mut term);
+    }
+
+    fn fmt_term(&mut self, plus: Option<&Node<'a>>, term: &mut Vec<&Node<'a>>) {
+        if term.is_empty() && plus.is_none() {
+            return
+        }
+
+        self.newline();
+
+        if let Some(plus) = plus {
+            self.fmt_run(&[plus]);
+        }
+
+        self.fmt_run(term);
+        term.clear();
+    }
+
+    fn fmt_run(&mut self, run: &[&Node<'a>]) {
+        for node in run {
+            match &node.item {
+                Item::Token(token) => self.push_token(*token, false),
+                Item::Group(group) => self.fmt_group(group),
+                item => self.push_comment(item),
+            }
+        }
+    }
+
+    fn fmt_group(&mut self, group: &Group<'a>) {
+        if !group.has_blocks() && self.put_flat_group(group) {
+            return
+        }
+
+        self.push_token(group.open, group.is_attached);
+        self.indent += 1;
+
+        match group.kind {
+            GroupKind::Fields { trailing_comma } => self.fmt_fields(&group.nodes, trailing_comma),
+            GroupKind::Rex => self.fmt_rex_contents(&group.nodes),
+        }
+
+        self.indent -= 1;
+        self.newline();
+        self.push_token(group.close, false);
+    }
+
+    /// Puts `group` in the current line, if it has no comments and
+    /// fits.
+    fn put_flat_group(&mut self, group: &Group<'a>) -> bool {
+        // The group is formatted alone, so that the spacing before it
+        // is decided here.
+        let mut flat = String::new();
+
+        if fmt_flat_group(group, &mut flat, &mut None).is_none() {
+            return false
+        }
+
+        let with_space =
+            matches!(self.last, Some(prev) if needs_space(prev, group.open, group.is_attached));
+        let width = if self.line.is_empty() {
+            self.indent * self.config.tab_spaces
+        } else {
+            self.width() + usize::from(with_space)
+        };
+
+        if width + flat.chars().count() > self.config.max_width {
+            return false
+        }
+
+        self.push_str(&flat, with_space);
+        self.last = Some(group.close);
+
+        true
+    }
+
+    /// Formats comma-separated fields of a broken group, one field per
+    /// line.
+    fn fmt_fields(&mut self, nodes: &[Node<'a>], trailing_comma: bool) {
+        let mut fields: Vec<Vec<&Node>> = vec![Vec::new()];
+
+        for node in nodes {
+            if let Item::Token(Token::Comma) = node.item {
+                fields.push(Vec::new());
+            } else if let Some(field) = fields.last_mut() {
+                field.push(node);
+            }
+        }
+
+        let num_fields = fields.len();
+
+        for (num, field) in fields.into_iter().enumerate() {
+            let mut field = field.as_slice();
+
+            // Comments trailing the previous field's comma.
+            while let Some((head, rest)) = field.split_first() {
+                if let Item::TrailingComment(_) = head.item {
+                    self.push_comment(&head.item);
+                    field = rest;
+                } else {
+                    break
+                }
+            }
+
+            let body_len =
+                field.iter().rposition(|node| !matches!(node.item, Item::TrailingComment(_)));
+            let (body, tail) = match body_len {
+                Some(pos) => field.split_at(pos + 1),
+                None => (field, &[][..]),
+            };
+
+            if body.iter().all(|node| node.item.is_comment()) {
+                for node in body {
+                    self.push_comment(&node.item);
+                }
+                continue
+            }
+
+            if let Some(head) = body.first() {
+                if num > 0 && head.is_spaced {
+                    self.blank_line();
+                }
+            }
+
+            self.newline();
+            self.fmt_run(body);
+
+            if trailing_comma || num + 1 < num_fields {
+                self.push_token(Token::Comma, false);
+            }
+
+            for node in tail {
+                self.push_comment(&node.item);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let script = r#"// Arrows.
+ces Arrow ( x : Dot,y:Dot ) {x=>y
+caps{2 x,3 y,}} // trailing
+use "lib/arrows.ces"
+use "lib/gates.ces"
+/// Main structure.
+ces Main { Arrow!( a , b ) +{ b=>c } }
+  // Labels.
+  vis { labels: { a: "Source" // source
+  , b: "Sink" } }
+"#;
+        let formatter = Formatter::new();
+        let formatted = formatter.format(script).unwrap();
+
+        assert_eq!(
+            formatted,
+            r#"// Arrows.
+ces Arrow(x: Dot, y: Dot) {
+    x => y
+    caps { 2 x, 3 y }
+} // trailing
+
+use "lib/arrows.ces"
+use "lib/gates.ces"
+
+/// Main structure.
+ces Main { Arrow!(a, b) + { b => c } }
+
+// Labels.
+vis {
+    labels: {
+        a: "Source", // source
+        b: "Sink",
+    },
+}
+"#
+        );
+        assert!(formatter.check(&formatted).unwrap());
+        assert!(!formatter.check(script).unwrap());
+    }
+
+    #[test]
+    fn test_format_width() {
+        let script = "ces Main { Left() + { a => b c } + Right!(d, e) }";
+        let formatter = Formatter::new().with_config(FormatterConfig::new().with_max_width(30));
+
+        assert_eq!(
+            formatter.format(script).unwrap(),
+            "ces Main {\n    Left()\n    + { a => b c }\n    + Right!(d, e)\n}\n"
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use std::{fmt, convert::TryFrom, str::FromStr};
 use logos::Logos;
-use enquote::unquote;
-use crate::{Weight, AscesisError, AscesisErrorKind};
+use enquote::{enquote, unquote};
+use crate::{Capacity, Weight, AscesisError, AscesisErrorKind};
 
 #[derive(Clone, Copy, PartialEq, Logos, Debug)]
 pub enum Token<'input> {
@@ -9,11 +9,15 @@ pub enum Token<'input> {
     Error,
     #[regex(r"\p{White_Space}", logos::skip)]
     WhiteSpace,
-    // FIXME trim
-    #[regex(r"///.*\n", |lex| lex.slice().strip_prefix("///").unwrap_or(""))]
+    // Only the `///` marker and a single space following it are
+    // stripped, so that indentation within documentation is kept.
+    #[regex(r"///.*\n", |lex| {
+        let text = lex.slice().trim_end().strip_prefix("///").unwrap_or("");
+        text.strip_prefix(' ').unwrap_or(text)
+    })]
     DocComment(&'input str),
-    #[regex(r"//.*\n", logos::skip)]
-    Comment,
+    #[regex(r"//.*\n", |lex| lex.slice().trim_end())]
+    Comment(&'input str),
     #[regex(r"[A-Za-z_][A-Za-z0-9_-]*", |lex| lex.slice())]
     Identifier(&'input str),
     #[regex(r"[0-9]+", |lex| lex.slice())]
@@ -34,6 +38,8 @@ pub enum Token<'input> {
     Dot,
     #[token(":")]
     Colon,
+    #[token("::")]
+    PathSep,
     #[token("{")]
     OpenCurly,
     #[token("}")]
@@ -62,6 +68,8 @@ pub enum Token<'input> {
     Bang,
     #[token("ces")]
     Ces,
+    #[token("use")]
+    Use,
     #[token("vis")]
     Vis,
     #[token("sat")]
@@ -88,7 +96,7 @@ impl<'input> fmt::Display for Token<'input> {
             Error => write!(f, "<error>"),
             WhiteSpace => write!(f, "<white-space>"),
             DocComment(_) => write!(f, "<doc-comment>"),
-            Comment => write!(f, "<comment>"),
+            Comment(_) => write!(f, "<comment>"),
             Identifier(id) => write!(f, "{}", id),
             LiteralFiniteSize(s) => write!(f, "{}", s),
             LiteralName(s) => write!(f, "\"{}\"", s),
@@ -98,10 +106,11 @@ impl<'input> fmt::Display for Token<'input> {
             Comma => write!(f, ","),
             Dot => write!(f, "."),
             Colon => write!(f, ":"),
+            PathSep => write!(f, "::"),
             OpenCurly => write!(f, "{{"),
             CloseCurly => write!(f, "}}"),
             OpenParen => write!(f, "("),
-            CloseParen => write!(f, "("),
+            CloseParen => write!(f, ")"),
             OpenBracket => write!(f, "["),
             CloseBracket => write!(f, "]"),
             Add => write!(f, "+"),
@@ -112,6 +121,7 @@ impl<'input> fmt::Display for Token<'input> {
             FatTwowayArrow => write!(f, "<=>"),
             Bang => write!(f, "!"),
             Ces => write!(f, "ces"),
+            Use => write!(f, "use"),
             Vis => write!(f, "vis"),
             Sat => write!(f, "sat"),
             Caps => write!(f, "caps"),
@@ -129,17 +139,29 @@ impl<'input> From<Token<'input>> for String {
         use Token::*;
 
         match token {
-            DocComment(s) | Identifier(s) | LiteralFiniteSize(s) | LiteralName(s) => s.into(),
+            DocComment(s) | Comment(s) | Identifier(s) | LiteralFiniteSize(s) | LiteralName(s) => {
+                s.into()
+            }
             _ => format!("{}", token),
         }
     }
 }
 
-pub struct Lexer<'input>(logos::Lexer<'input, Token<'input>>);
+pub struct Lexer<'input> {
+    inner:         logos::Lexer<'input, Token<'input>>,
+    with_comments: bool,
+}
 
 impl<'input> Lexer<'input> {
     pub fn new(input: &'input str) -> Self {
-        Lexer(Token::lexer(input))
+        Lexer { inner: Token::lexer(input), with_comments: false }
+    }
+
+    /// Creates a `Lexer` which yields [`Token::Comment`]s, instead of
+    /// skipping them.  Such a `Lexer` isn't meant to be used for
+    /// parsing.
+    pub fn with_comments(input: &'input str) -> Self {
+        Lexer { inner: Token::lexer(input), with_comments: true }
     }
 }
 
@@ -147,9 +169,10 @@ impl<'input> Iterator for Lexer<'input> {
     type Item = Result<(usize, Token<'input>, usize), AscesisError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let lexer = &mut self.0;
+        let with_comments = self.with_comments;
+        let lexer = &mut self.inner;
 
-        lexer.next().map(|token| {
+        lexer.find(|token| with_comments || !matches!(token, Token::Comment(_))).map(|token| {
             let span = lexer.span();
 
             match token {
@@ -192,6 +215,17 @@ impl Literal {
     }
 }
 
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Literal::Size(size) => size.fmt(f),
+            Literal::Omega => write!(f, "ω"),
+            Literal::Theta => write!(f, "θ"),
+            Literal::Name(name) => write!(f, "{}", enquote('"', name)),
+        }
+    }
+}
+
 impl TryFrom<Literal> for u64 {
     type Error = AscesisError;
 
@@ -230,6 +264,20 @@ impl TryFrom<Literal> for Weight {
     }
 }
 
+impl TryFrom<Literal> for Capacity {
+    type Error = AscesisError;
+
+    fn try_from(lit: Literal) -> Result<Self, Self::Error> {
+        match lit {
+            Literal::Size(sz) => {
+                Capacity::finite(sz).ok_or_else(|| AscesisErrorKind::SizeLiteralOverflow.into())
+            }
+            Literal::Omega => Ok(Capacity::omega()),
+            _ => Err(AscesisErrorKind::ExpectedSizeLiteral.into()),
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum BinOp {
     Add,
@@ -20,29 +20,47 @@ lalrpop_mod!(
 );
 
 mod error;
+mod diagnostic;
+mod span;
+mod spelling;
 mod bnf;
 pub mod grammar;
 pub mod sentence;
 mod axiom;
 mod ces;
+mod template;
 mod context;
 mod content;
+mod equivalence;
+mod coherence;
 mod rex;
 mod polynomial;
 mod domain;
 mod lexer;
+mod formatter;
+#[cfg(feature = "lsp")]
+pub mod lsp;
 
 pub use aces::*;
 
-pub use error::{AscesisError, AscesisErrorKind};
+pub use error::{AscesisError, AscesisErrorKind, AscesisWarning, AscesisWarningKind};
+pub use diagnostic::{Diagnostic, Severity, Label, Suggestion};
+pub use span::Span;
 pub use axiom::Axiom;
-pub use ces::{CesFile, CesFileBlock, CesName, ToCesName, ImmediateDef, CesImmediate, CesInstance};
+pub use ces::{
+    CesFile, CesFileBlock, UseDirective, CesName, ToCesName, ImmediateDef, CesImmediate,
+    CesInstance,
+};
+pub use template::{TemplateDef, ArgDecl, ArgType, ArgValue};
 pub use context::{
     PropBlock, PropSelector, PropValue, CapacitiesBlock, UnboundedBlock, WeightsBlock,
     InhibitorsBlock, WeightlessBlock,
 };
 pub use content::AscesisFormat;
+pub use equivalence::{Difference, compare_contents, compare_rexes};
+pub use coherence::IncoherentLink;
 pub use rex::{Rex, ThinArrowRule, FatArrowRule};
 pub use polynomial::Polynomial;
 pub use domain::{DotName, ToDotName, DotList};
 pub use lexer::{Lexer, Token, Literal, BinOp};
+pub use formatter::{Formatter, FormatterConfig};
@@ -0,0 +1,719 @@
+//! A [Language Server Protocol] server for _Ascesis_ scripts.
+//!
+//! The server communicates over a pair of byte streams (standard
+//! input and output of the `ascesis-lsp` binary) and supports
+//!
+//! - diagnostics published whenever a document is opened or changed,
+//!   including errors found by compiling the document;
+//! - go-to-definition from `Name()` and `Name!(...)` to the
+//!   definition of `Name` in the same document;
+//! - hover showing the doc comment of a structure and its thin arrow
+//!   normal form;
+//! - completion of names of defined structures and of context-block
+//!   keywords.
+//!
+//! Documents are synchronised in full, i.e. each change replaces the
+//! entire text of a document.
+//!
+//! [Language Server Protocol]: https://microsoft.github.io/language-server-protocol/
+
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+    error::Error,
+};
+use serde_json::{json, Value};
+use aces::{Context, CompilableMut};
+use crate::{
+    CesFile, CesFileBlock, ImmediateDef, TemplateDef, Lexer, Token, Span, AscesisError, Diagnostic,
+    Severity,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+const COMPLETION_KIND_FUNCTION: u64 = 3;
+const COMPLETION_KIND_KEYWORD: u64 = 14;
+const COMPLETION_KIND_STRUCT: u64 = 22;
+
+/// Keywords introducing context blocks, offered as completions.
+const BLOCK_KEYWORDS: [&str; 8] =
+    ["vis", "sat", "caps", "unbounded", "weights", "inhibit", "activate", "drop"];
+
+/// Name of the structure compiled for diagnostics, if defined.
+const DEFAULT_ROOT_NAME: &str = "Main";
+
+#[derive(Debug)]
+struct Document {
+    script:   String,
+    path:     Option<PathBuf>,
+    /// The result of parsing the latest version of this document which
+    /// was free of syntax errors.
+    ces_file: Option<CesFile>,
+}
+
+/// A reference to, or the definition of, a structure or template,
+/// found by scanning the tokens of a script.
+#[derive(Clone, Debug)]
+struct Symbol {
+    name:          String,
+    span:          Span,
+    is_definition: bool,
+}
+
+#[derive(Default, Debug)]
+pub struct LanguageServer {
+    documents:       BTreeMap<String, Document>,
+    is_shutdown:     bool,
+    is_exit_pending: bool,
+}
+
+impl LanguageServer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns `true` after the `exit` notification was received.
+    #[inline]
+    pub fn is_exiting(&self) -> bool {
+        self.is_exit_pending
+    }
+
+    /// Returns the process exit code expected by the client: zero if
+    /// the `exit` notification was preceded by a `shutdown` request,
+    /// and one otherwise.
+    #[inline]
+    pub fn get_exit_code(&self) -> i32 {
+        if self.is_shutdown {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Serves messages read from `reader`, writing responses and
+    /// notifications to `writer`, until the `exit` notification is
+    /// received or `reader` is exhausted.
+    pub fn run<R: BufRead, W: Write>(&mut self, mut reader: R, mut writer: W) -> io::Result<()> {
+        while !self.is_exiting() {
+            let content = match read_message(&mut reader)? {
+                Some(content) => content,
+                None => break,
+            };
+
+            let replies = match serde_json::from_slice(&content) {
+                Ok(message) => self.handle_message(message),
+                Err(err) => vec![error_response(Value::Null, PARSE_ERROR, err.to_string())],
+            };
+
+            for reply in replies {
+                write_message(&mut writer, &reply)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles a single JSON-RPC message, returning the response, if
+    /// `message` is a request, followed by notifications to be sent to
+    /// the client.
+    pub fn handle_message(&mut self, message: Value) -> Vec<Value> {
+        let method = match message.get("method").and_then(Value::as_str) {
+            Some(method) => method,
+            None => {
+                // Responses to server requests are not expected.
+                return Vec::new()
+            }
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        if let Some(id) = message.get("id").cloned() {
+            let result = match method {
+                "initialize" => Ok(self.initialize()),
+                "shutdown" => {
+                    self.is_shutdown = true;
+                    Ok(Value::Null)
+                }
+                _ if self.is_shutdown => {
+                    Err((INVALID_REQUEST, "Server is shutting down".to_owned()))
+                }
+                "textDocument/definition" => self.definition(&params),
+                "textDocument/hover" => self.hover(&params),
+                "textDocument/completion" => self.completion(&params),
+                _ => Err((METHOD_NOT_FOUND, format!("Unsupported method '{}'", method))),
+            };
+
+            match result {
+                Ok(result) => vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })],
+                Err((code, message)) => vec![error_response(id, code, message)],
+            }
+        } else {
+            match method {
+                "exit" => {
+                    self.is_exit_pending = true;
+                    Vec::new()
+                }
+                "textDocument/didOpen" => {
+                    let document = &params["textDocument"];
+
+                    match (document["uri"].as_str(), document["text"].as_str()) {
+                        (Some(uri), Some(text)) => self.update_document(uri, text.to_owned()),
+                        _ => Vec::new(),
+                    }
+                }
+                "textDocument/didChange" => {
+                    let uri = params["textDocument"]["uri"].as_str();
+                    let text = params["contentChanges"]
+                        .as_array()
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change["text"].as_str());
+
+                    match (uri, text) {
+                        (Some(uri), Some(text)) => self.update_document(uri, text.to_owned()),
+                        _ => Vec::new(),
+                    }
+                }
+                "textDocument/didClose" => {
+                    if let Some(uri) = params["textDocument"]["uri"].as_str() {
+                        self.documents.remove(uri);
+
+                        vec![diagnostics_notification(uri, Vec::new())]
+                    } else {
+                        Vec::new()
+                    }
+                }
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    fn initialize(&self) -> Value {
+        json!({
+            "capabilities": {
+                "textDocumentSync": 1,
+                "definitionProvider": true,
+                "hoverProvider": true,
+                "completionProvider": {},
+            },
+            "serverInfo": {
+                "name": "ascesis-lsp",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+        })
+    }
+
+    /// Replaces the text of a document, and returns the notification
+    /// publishing its diagnostics.
+    fn update_document(&mut self, uri: &str, script: String) -> Vec<Value> {
+        let path = uri_to_path(uri);
+        let (ces_file, errors) = check_script(&script, path.as_ref());
+        let diagnostics = errors
+            .iter()
+            .map(|diagnostic| {
+                let span = diagnostic.get_primary_span().unwrap_or_default();
+                let related: Vec<_> = diagnostic
+                    .get_labels()
+                    .iter()
+                    .filter(|label| !label.is_primary())
+                    .map(|label| {
+                        json!({
+                            "location": { "uri": uri, "range": span_to_range(&script, label.get_span()) },
+                            "message": label.get_message().unwrap_or(""),
+                        })
+                    })
+                    .collect();
+                let mut message = diagnostic.get_message().to_owned();
+
+                for note in diagnostic.get_notes() {
+                    message.push_str("\nnote: ");
+                    message.push_str(note);
+                }
+
+                let mut result = json!({
+                    "range": span_to_range(&script, span),
+                    "severity": lsp_severity(diagnostic.get_severity()),
+                    "source": "ascesis",
+                    "message": message,
+                    "relatedInformation": related,
+                });
+
+                if let Some(code) = diagnostic.get_code() {
+                    result["code"] = json!(code);
+                }
+
+                result
+            })
+            .collect();
+
+        let document = self.documents.entry(uri.to_owned()).or_insert_with(|| Document {
+            script:   String::new(),
+            path:     None,
+            ces_file: None,
+        });
+
+        document.script = script;
+        document.path = path;
+
+        if ces_file.is_some() {
+            document.ces_file = ces_file;
+        }
+
+        vec![diagnostics_notification(uri, diagnostics)]
+    }
+
+    /// Returns the document and the offset of a position given in the
+    /// parameters of a request.
+    fn get_position<'a>(
+        &'a self,
+        params: &'a Value,
+    ) -> Result<(&'a str, &'a Document, usize), (i64, String)> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .ok_or_else(|| (INVALID_PARAMS, "Missing document URI".to_owned()))?;
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("Unknown document '{}'", uri)))?;
+        let position = &params["position"];
+        let line = position["line"]
+            .as_u64()
+            .ok_or_else(|| (INVALID_PARAMS, "Missing position line".to_owned()))?;
+        let character = position["character"]
+            .as_u64()
+            .ok_or_else(|| (INVALID_PARAMS, "Missing position character".to_owned()))?;
+        let offset = position_to_offset(&document.script, line as usize, character as usize);
+
+        Ok((uri, document, offset))
+    }
+
+    fn definition(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (uri, document, offset) = self.get_position(params)?;
+        let symbols = scan_symbols(&document.script);
+
+        let location = symbol_at(&symbols, offset)
+            .and_then(|symbol| {
+                symbols.iter().find(|other| other.is_definition && other.name == symbol.name)
+            })
+            .map(|target| json!({ "uri": uri, "range": span_to_range(&document.script, target.span) }));
+
+        Ok(location.unwrap_or(Value::Null))
+    }
+
+    fn hover(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (_, document, offset) = self.get_position(params)?;
+        let symbols = scan_symbols(&document.script);
+
+        let hover = symbol_at(&symbols, offset).and_then(|symbol| {
+            let ces_file = document.ces_file.as_ref()?;
+            let (doc, fit) = describe_definition(ces_file, &symbol.name)?;
+            let mut value = String::new();
+
+            if let Some(doc) = doc {
+                value.push_str(doc);
+                value.push_str("\n\n");
+            }
+            value.push_str("```ascesis\n");
+            value.push_str(&fit);
+            value.push_str("\n```");
+
+            Some(json!({
+                "contents": { "kind": "markdown", "value": value },
+                "range": span_to_range(&document.script, symbol.span),
+            }))
+        });
+
+        Ok(hover.unwrap_or(Value::Null))
+    }
+
+    fn completion(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (_, document, _) = self.get_position(params)?;
+        let mut items = Vec::new();
+
+        if let Some(ref ces_file) = document.ces_file {
+            for block in ces_file.get_blocks() {
+                match block {
+                    CesFileBlock::Imm(imm) => items.push(completion_item(
+                        imm.get_name().as_str(),
+                        COMPLETION_KIND_STRUCT,
+                        "structure",
+                        imm.get_doc(),
+                    )),
+                    CesFileBlock::Template(template) => {
+                        let args: Vec<_> =
+                            template.get_args().iter().map(ToString::to_string).collect();
+                        let detail = format!("{}({})", template.get_name(), args.join(", "));
+
+                        items.push(completion_item(
+                            template.get_name().as_str(),
+                            COMPLETION_KIND_FUNCTION,
+                            &detail,
+                            template.get_doc(),
+                        ))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for keyword in BLOCK_KEYWORDS.iter() {
+            items.push(completion_item(keyword, COMPLETION_KIND_KEYWORD, "context block", None));
+        }
+
+        Ok(Value::Array(items))
+    }
+}
+
+fn completion_item(label: &str, kind: u64, detail: &str, doc: Option<&str>) -> Value {
+    let mut item = json!({ "label": label, "kind": kind, "detail": detail });
+
+    if let Some(doc) = doc {
+        item["documentation"] = json!({ "kind": "markdown", "value": doc });
+    }
+
+    item
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn diagnostics_notification(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// Reads the content part of a message, or returns `None` at the end
+/// of input.
+fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut content_length = None;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None)
+        }
+
+        let header = line.trim_end();
+
+        if header.is_empty() {
+            if content_length.is_some() {
+                break
+            } else {
+                continue
+            }
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length =
+                    Some(value.trim().parse::<usize>().map_err(|err| {
+                        io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+                    })?);
+            }
+        }
+    }
+
+    let mut content = vec![0; content_length.unwrap_or(0)];
+
+    reader.read_exact(&mut content)?;
+
+    Ok(Some(content))
+}
+
+fn write_message<W: Write>(writer: &mut W, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+
+    write!(writer, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    writer.flush()
+}
+
+/// Parses and compiles `script`, returning the parsing result, if
+/// successful, and diagnostics of all errors and warnings found.
+/// A script with syntax errors isn't compiled, and its parsing result
+/// is made of the parts which were parsed successfully.
+///
+/// The structure compiled is the one named `Main`, or the first one
+/// defined, if there is no `Main`.
+fn check_script(script: &str, path: Option<&PathBuf>) -> (Option<CesFile>, Vec<Diagnostic>) {
+    let ces_file = match CesFile::from_script_recovering(script) {
+        Ok((ces_file, None)) => ces_file,
+        Ok((ces_file, Some(err))) => return (Some(ces_file), err.to_diagnostics()),
+        Err(err) => return (None, to_diagnostics(err)),
+    };
+
+    let root_name = ces_file
+        .get_blocks()
+        .iter()
+        .filter_map(|block| {
+            if let CesFileBlock::Imm(imm) = block {
+                Some(imm.get_name().as_str())
+            } else {
+                None
+            }
+        })
+        .fold(None, |found, name| match found {
+            Some(DEFAULT_ROOT_NAME) => found,
+            Some(_) if name != DEFAULT_ROOT_NAME => found,
+            _ => Some(name),
+        })
+        .map(ToOwned::to_owned);
+
+    let mut diagnostics: Vec<_> =
+        ces_file.get_warnings().iter().map(|warning| warning.to_diagnostic()).collect();
+
+    if let Some(root_name) = root_name {
+        // Compilation consumes the file, hence a fresh copy is parsed.
+        if let Ok(mut compiled) = CesFile::from_script(script) {
+            if let Some(path) = path {
+                compiled.set_path(path);
+            }
+
+            let ctx = Context::new_toplevel(root_name.as_str());
+            let result =
+                compiled.set_root_name(root_name.as_str()).and_then(|_| compiled.compile_mut(&ctx));
+
+            if let Err(err) = result {
+                diagnostics.extend(to_diagnostics(err));
+            }
+        }
+    }
+
+    (Some(ces_file), diagnostics)
+}
+
+fn lsp_severity(severity: Severity) -> u64 {
+    match severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+        Severity::Note => 3,
+    }
+}
+
+fn to_diagnostics(err: Box<dyn Error>) -> Vec<Diagnostic> {
+    match err.downcast::<AscesisError>() {
+        Ok(err) => err.to_diagnostics(),
+        Err(err) => vec![Diagnostic::error(err.to_string())],
+    }
+}
+
+/// Returns the doc comment and the thin arrow normal form of the
+/// structure or template of a given name defined in `ces_file`.
+fn describe_definition<'a>(ces_file: &'a CesFile, name: &str) -> Option<(Option<&'a str>, String)> {
+    ces_file.get_blocks().iter().find_map(|block| match block {
+        CesFileBlock::Imm(imm) if imm.get_name().as_str() == name => {
+            let fit = ImmediateDef::new(imm.get_name().clone(), imm.get_rex().fit_clone());
+
+            Some((imm.get_doc(), fit.to_string()))
+        }
+        CesFileBlock::Template(template) if template.get_name().as_str() == name => {
+            let fit = TemplateDef::new(
+                template.get_name().clone(),
+                template.get_args().to_vec(),
+                template.get_rex().fit_clone(),
+            )
+            .ok()?;
+
+            Some((template.get_doc(), fit.to_string()))
+        }
+        _ => None,
+    })
+}
+
+/// Finds all definitions of, and references to, structures and
+/// templates in `script`.
+///
+/// A definition is a name following the `ces` keyword.  A reference
+/// is a (possibly qualified) name followed by `()` or `!`.  Scanning
+/// tokens, instead of walking the syntax tree, keeps symbols
+/// available while a script doesn't parse.
+fn scan_symbols(script: &str) -> Vec<Symbol> {
+    let tokens: Vec<_> = Lexer::new(script).filter_map(Result::ok).collect();
+    let mut symbols = Vec::new();
+    let mut ndx = 0;
+
+    while ndx < tokens.len() {
+        if let (start, Token::Identifier(head), end) = tokens[ndx] {
+            let mut name = head.to_owned();
+            let mut span = Span::new(start, end);
+            let mut next = ndx + 1;
+
+            if let (Some((_, Token::PathSep, _)), Some((_, Token::Identifier(tail), tail_end))) =
+                (tokens.get(next), tokens.get(next + 1))
+            {
+                name = format!("{}::{}", head, tail);
+                span = Span::new(start, *tail_end);
+                next += 2;
+            }
+
+            let is_definition = ndx > 0 && matches!(tokens[ndx - 1].1, Token::Ces);
+            let is_reference = matches!(
+                (tokens.get(next), tokens.get(next + 1)),
+                (Some((_, Token::Bang, _)), _)
+                    | (Some((_, Token::OpenParen, _)), Some((_, Token::CloseParen, _)))
+            );
+
+            if is_definition || is_reference {
+                symbols.push(Symbol { name, span, is_definition });
+            }
+
+            ndx = next;
+        } else {
+            ndx += 1;
+        }
+    }
+
+    symbols
+}
+
+fn symbol_at(symbols: &[Symbol], offset: usize) -> Option<&Symbol> {
+    symbols.iter().find(|symbol| symbol.span.start() <= offset && offset <= symbol.span.end())
+}
+
+/// Converts a byte offset into an LSP position, i.e. a zero-based line
+/// number and a character offset counted in UTF-16 code units.
+fn offset_to_position(script: &str, offset: usize) -> Value {
+    let offset = offset.min(script.len());
+    let before = script.get(..offset).unwrap_or(script);
+    let line_start = before.rfind('\n').map_or(0, |pos| pos + 1);
+    let line = before.matches('\n').count();
+    let character: usize = before[line_start..].chars().map(char::len_utf16).sum();
+
+    json!({ "line": line, "character": character })
+}
+
+fn span_to_range(script: &str, span: Span) -> Value {
+    json!({
+        "start": offset_to_position(script, span.start()),
+        "end": offset_to_position(script, span.end().max(span.start())),
+    })
+}
+
+/// Converts an LSP position into a byte offset, clamped to the end of
+/// the line, or of the script.
+fn position_to_offset(script: &str, line: usize, character: usize) -> usize {
+    let mut line_start = 0;
+
+    for _ in 0..line {
+        match script[line_start..].find('\n') {
+            Some(pos) => line_start += pos + 1,
+            None => return script.len(),
+        }
+    }
+
+    let mut units = 0;
+
+    for (pos, ch) in script[line_start..].char_indices() {
+        if units >= character || ch == '\n' {
+            return line_start + pos
+        }
+        units += ch.len_utf16();
+    }
+
+    script.len()
+}
+
+/// Converts a `file` URI into a path, or returns `None` for URIs of
+/// other schemes.
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let encoded = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' && tail.len() >= 2 {
+            if let Ok(decoded) =
+                u8::from_str_radix(std::str::from_utf8(&tail[..2]).unwrap_or(""), 16)
+            {
+                bytes.push(decoded);
+                rest = &tail[2..];
+                continue
+            }
+        }
+        bytes.push(byte);
+        rest = tail;
+    }
+
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "untitled:test.ces";
+
+    fn open(server: &mut LanguageServer, text: &str) -> Vec<Value> {
+        server.handle_message(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {
+                "textDocument": { "uri": URI, "languageId": "ascesis", "version": 1, "text": text },
+            },
+        }))
+    }
+
+    fn request(server: &mut LanguageServer, method: &str, line: u64, character: u64) -> Value {
+        let mut replies = server.handle_message(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+            },
+        }));
+
+        replies.remove(0)["result"].take()
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut server = LanguageServer::new();
+
+        let replies = open(&mut server, "ces Main { a => b }\n");
+        assert_eq!(replies[0]["params"]["diagnostics"], json!([]));
+
+        let replies = open(&mut server, "ces Main {\n    { a => b } + Missing()\n}\n");
+        let diagnostics = &replies[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics.as_array().map(Vec::len), Some(1));
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 1, "character": 17 }));
+
+        let replies = open(&mut server, "ces Main { a => }\n");
+        let diagnostics = &replies[0]["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 0, "character": 16 }));
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut server = LanguageServer::new();
+
+        open(
+            &mut server,
+            "/// An arrow.\nces Arrow { a => b }\n\nces Main { Arrow() + { c -> d } }\n",
+        );
+
+        let location = request(&mut server, "textDocument/definition", 3, 12);
+        assert_eq!(location["range"]["start"], json!({ "line": 1, "character": 4 }));
+        assert_eq!(location["range"]["end"], json!({ "line": 1, "character": 9 }));
+
+        let hover = request(&mut server, "textDocument/hover", 3, 12);
+        let value = hover["contents"]["value"].as_str().unwrap();
+        assert!(value.starts_with("An arrow.\n"));
+        assert!(value.contains("ces Arrow { { a -> b } + { b <- a } }"));
+
+        assert_eq!(request(&mut server, "textDocument/definition", 3, 23), Value::Null);
+
+        let items = request(&mut server, "textDocument/completion", 3, 0);
+        let labels: Vec<_> = items.as_array().unwrap().iter().map(|item| &item["label"]).collect();
+        assert!(labels.contains(&&json!("Arrow")));
+        assert!(labels.contains(&&json!("weights")));
+    }
+}
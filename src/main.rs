@@ -0,0 +1,900 @@
+#[macro_use]
+extern crate log;
+
+use std::{
+    fs, fmt,
+    io::{self, Read, BufRead, Write},
+    fmt::Write as _,
+};
+use rand::{thread_rng, Rng};
+use fern::colors::{Color, ColoredLevelConfig};
+use std::error::Error;
+use ascesis::{
+    CesFile, CesFileBlock, ImmediateDef, TemplateDef, Rex, Formatter, Axiom, Context,
+    ContextHandle, Content, CompilableMut, DotId, Lexer, Token, AscesisError, Diagnostic,
+    compare_contents, compare_rexes, grammar::Grammar, sentence::Generator,
+};
+
+/// Exit code for scripts which fail to parse or compile, and for
+/// files found unformatted by `fmt --check`.
+const EXIT_INVALID: i32 = 1;
+
+/// Exit code for all other failures, e.g. I/O errors or invalid
+/// command-line arguments.
+const EXIT_FAILURE: i32 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
+
+#[derive(Debug)]
+struct AppError {
+    message:     String,
+    code:        i32,
+    diagnostics: Vec<Diagnostic>,
+    script:      Option<String>,
+    origin:      Option<String>,
+}
+
+impl AppError {
+    fn invalid<E: fmt::Display>(err: E) -> Self {
+        AppError {
+            message:     err.to_string(),
+            code:        EXIT_INVALID,
+            diagnostics: Vec::new(),
+            script:      None,
+            origin:      None,
+        }
+    }
+
+    fn failure<E: fmt::Display>(err: E) -> Self {
+        AppError { code: EXIT_FAILURE, ..AppError::invalid(err) }
+    }
+
+    /// Creates an error for a script which failed to parse or compile,
+    /// described by diagnostics, if `err` is an [`AscesisError`].
+    fn in_script(err: Box<dyn Error>, origin: Option<&str>) -> Self {
+        match err.downcast::<AscesisError>() {
+            Ok(err) => AppError {
+                diagnostics: err.to_diagnostics(),
+                script: err.get_script().map(ToOwned::to_owned),
+                origin: origin.map(ToOwned::to_owned),
+                ..AppError::invalid(&err)
+            },
+            Err(err) => AppError::invalid(err),
+        }
+    }
+
+    /// Writes this error to the standard error in human readable
+    /// form, or as JSON diagnostics, one per line, to the standard
+    /// output.
+    fn report(&self, format: ErrorFormat) {
+        match format {
+            ErrorFormat::Human if self.diagnostics.is_empty() => error!("{}", self),
+            ErrorFormat::Json if self.diagnostics.is_empty() => {
+                println!("{}", Diagnostic::error(self.message.as_str()).to_json(None));
+            }
+            _ => report_diagnostics(
+                &self.diagnostics,
+                self.script.as_deref(),
+                self.origin.as_deref(),
+                format,
+            ),
+        }
+    }
+}
+
+/// Writes `diagnostics` to the standard error in human readable form,
+/// or as JSON, one per line, to the standard output.
+fn report_diagnostics(
+    diagnostics: &[Diagnostic],
+    script: Option<&str>,
+    origin: Option<&str>,
+    format: ErrorFormat,
+) {
+    for diagnostic in diagnostics {
+        match format {
+            ErrorFormat::Human => eprintln!("{}", diagnostic.render(script, origin)),
+            ErrorFormat::Json => println!("{}", diagnostic.to_json(script)),
+        }
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(err: io::Error) -> Self {
+        AppError::failure(err)
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.message.fmt(f)
+    }
+}
+
+/// Reads a script from `path`, or from the standard input, if `path`
+/// is `-`.
+fn read_script(path: &str) -> Result<String, AppError> {
+    if path == "-" {
+        let mut script = String::new();
+
+        io::stdin().read_to_string(&mut script)?;
+
+        Ok(script)
+    } else {
+        fs::read_to_string(path).map_err(|err| AppError::failure(format!("{}: {}", path, err)))
+    }
+}
+
+fn load_file(path: &str) -> Result<CesFile, AppError> {
+    let script = read_script(path)?;
+    let mut ces_file =
+        CesFile::from_script(script).map_err(|err| AppError::in_script(err, Some(path)))?;
+
+    if path != "-" {
+        ces_file.set_path(path);
+    }
+
+    Ok(ces_file)
+}
+
+fn compile_file(
+    ces_file: &mut CesFile,
+    root_name: &str,
+    origin: &str,
+) -> Result<ContextHandle, AppError> {
+    let in_script = |err| AppError::in_script(err, Some(origin));
+
+    ces_file.set_root_name(root_name).map_err(in_script)?;
+
+    let ctx = Context::new_toplevel(root_name);
+
+    ces_file.compile_mut(&ctx).map_err(in_script)?;
+
+    Ok(ctx)
+}
+
+fn cmd_parse(args: &clap::ArgMatches) -> Result<(), AppError> {
+    let ces_file = load_file(args.value_of("FILE").unwrap())?;
+
+    let is_ast = args.is_present("ast");
+
+    for block in ces_file.get_blocks() {
+        if is_ast {
+            println!("{:#?}", block);
+        } else {
+            println!("{}", block);
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_check(args: &clap::ArgMatches, error_format: ErrorFormat) -> Result<(), AppError> {
+    let path = args.value_of("FILE").unwrap();
+    let mut ces_file = load_file(path)?;
+
+    let ctx = compile_file(&mut ces_file, args.value_of("root").unwrap(), path)?;
+    let mut warnings = ces_file.get_warnings();
+
+    if args.is_present("coherence") {
+        let links = ces_file
+            .get_incoherent_links(&ctx)
+            .map_err(|err| AppError::in_script(err.into(), Some(path)))?;
+
+        warnings.extend(links.iter().map(|link| link.to_warning()));
+    }
+
+    let warnings: Vec<_> = warnings.iter().map(|warning| warning.to_diagnostic()).collect();
+
+    report_diagnostics(&warnings, ces_file.get_script(), Some(path), error_format);
+
+    if warnings.is_empty() {
+        info!("{} is valid", path);
+
+        Ok(())
+    } else if args.is_present("deny-warnings") {
+        Err(AppError::invalid(format!("{}: {} warning(s) denied", path, warnings.len())))
+    } else {
+        info!("{} is valid, with {} warning(s)", path, warnings.len());
+
+        Ok(())
+    }
+}
+
+fn cmd_fit(args: &clap::ArgMatches) -> Result<(), AppError> {
+    let ces_file = load_file(args.value_of("FILE").unwrap())?;
+    let root_name = args.value_of("root");
+    let mut is_found = false;
+
+    for block in ces_file.get_blocks() {
+        if let CesFileBlock::Imm(imm) = block {
+            if root_name.is_none() || root_name == Some(imm.get_name().as_str()) {
+                let fit = ImmediateDef::new(imm.get_name().clone(), imm.get_rex().fit_clone());
+
+                println!("{}", fit);
+                is_found = true;
+            }
+        }
+    }
+
+    if let (Some(name), false) = (root_name, is_found) {
+        Err(AppError::invalid(format!("Structure '{}' not found", name)))
+    } else {
+        Ok(())
+    }
+}
+
+/// Returns the compiled structure in Graphviz format.
+///
+/// Each dot is a node, and each effect monomial of a dot is an arrow,
+/// forking at an unlabeled point, if the monomial has more than one
+/// dot.
+fn to_graphviz(ces_file: &mut CesFile, ctx: &ContextHandle) -> String {
+    let dot_ids = ces_file.get_carrier_ids();
+    let title = ces_file.get_vis_name("title").or_else(|| ces_file.get_name()).unwrap_or("ces");
+    let ctx = ctx.lock().unwrap();
+    let name_of = |id| ctx.get_dot_name(id).unwrap_or("?");
+    let mut result = String::new();
+    let mut num_forks = 0;
+
+    writeln!(result, "digraph {:?} {{", title).unwrap();
+
+    for &id in dot_ids.iter() {
+        let name = name_of(id);
+        let label = ces_file.get_nested_vis_name(Some("labels"), name).unwrap_or(name);
+
+        writeln!(result, "    {:?} [label={:?}];", name, label).unwrap();
+    }
+
+    for &id in dot_ids.iter() {
+        for mono in ces_file.get_effects_by_id(id).into_iter().flatten() {
+            if let [single] = mono.as_slice() {
+                writeln!(result, "    {:?} -> {:?};", name_of(id), name_of(*single)).unwrap();
+            } else {
+                num_forks += 1;
+
+                let fork = format!("fork{}", num_forks);
+
+                writeln!(result, "    {:?} [shape=point];", fork).unwrap();
+                writeln!(result, "    {:?} -> {:?} [arrowhead=none];", name_of(id), fork).unwrap();
+
+                for &arm in mono.iter() {
+                    writeln!(result, "    {:?} -> {:?};", fork, name_of(arm)).unwrap();
+                }
+            }
+        }
+    }
+
+    result.push_str("}\n");
+
+    result
+}
+
+fn cmd_export(args: &clap::ArgMatches) -> Result<(), AppError> {
+    let path = args.value_of("FILE").unwrap();
+    let mut ces_file = load_file(path)?;
+
+    let output = match args.value_of("format").unwrap() {
+        "ces" => ces_file.to_string(),
+        "dot" => {
+            let ctx = compile_file(&mut ces_file, args.value_of("root").unwrap(), path)?;
+
+            to_graphviz(&mut ces_file, &ctx)
+        }
+        format => return Err(AppError::failure(format!("Unknown export format '{}'", format))),
+    };
+
+    if let Some(path) = args.value_of("output") {
+        fs::write(path, output)?;
+    } else {
+        print!("{}", output);
+    }
+
+    Ok(())
+}
+
+fn cmd_equiv(args: &clap::ArgMatches) -> Result<(), AppError> {
+    let lhs = args.value_of("LEFT").unwrap();
+    let rhs = args.value_of("RIGHT").unwrap();
+
+    let difference = if args.is_present("rex") {
+        let parse = |phrase: &str| phrase.parse::<Rex>().map_err(AppError::invalid);
+        let lhs = parse(lhs)?;
+        let rhs = parse(rhs)?;
+        let ctx = Context::new_toplevel("equiv");
+
+        compare_rexes(&lhs, &rhs, &ctx).map_err(|err| AppError::in_script(err, None))?
+    } else {
+        let lhs_root = args.value_of("root").unwrap();
+        let rhs_root = args.value_of("other-root").unwrap_or(lhs_root);
+        let mut lhs_file = load_file(lhs)?;
+        let mut rhs_file = load_file(rhs)?;
+        let lhs_ctx = compile_file(&mut lhs_file, lhs_root, lhs)?;
+        let rhs_ctx = compile_file(&mut rhs_file, rhs_root, rhs)?;
+
+        compare_contents(&mut lhs_file, &lhs_ctx, &mut rhs_file, &rhs_ctx)
+    };
+
+    if let Some(difference) = difference {
+        println!("{}", difference);
+
+        Err(AppError::invalid("Structures are not equivalent"))
+    } else {
+        println!("Structures are equivalent");
+
+        Ok(())
+    }
+}
+
+fn cmd_generate(args: &clap::ArgMatches) -> Result<(), AppError> {
+    let symbol = args.value_of("AXIOM").unwrap();
+    let axiom = Axiom::from_known_symbol(symbol)
+        .ok_or_else(|| AppError::failure(format!("Unknown axiom '{}'", symbol)))?;
+    let count: usize = args.value_of("count").unwrap().parse().map_err(AppError::failure)?;
+
+    let grammar = Grammar::of_ascesis();
+    let generator = Generator::new(&grammar);
+    let all_phrases: Vec<_> =
+        generator.rooted(axiom.symbol()).map_err(AppError::failure)?.iter().collect();
+
+    if all_phrases.is_empty() {
+        return Err(AppError::failure(format!("Random phrase generation failed for {:?}", axiom)))
+    }
+
+    let mut rng = thread_rng();
+
+    for _ in 0..count {
+        println!("{}", all_phrases[rng.gen_range(0, all_phrases.len())]);
+    }
+
+    Ok(())
+}
+
+fn cmd_fmt(args: &clap::ArgMatches) -> Result<(), AppError> {
+    let formatter = Formatter::new();
+    let is_check = args.is_present("check");
+    let mut num_unformatted = 0;
+
+    for path in args.values_of("FILE").unwrap() {
+        let script = read_script(path)?;
+        let formatted =
+            formatter.format(&script).map_err(|err| AppError::in_script(err, Some(path)))?;
+
+        if is_check {
+            if formatted != script {
+                println!("{} needs reformatting", path);
+                num_unformatted += 1;
+            }
+        } else if path == "-" {
+            print!("{}", formatted);
+        } else if formatted != script {
+            fs::write(path, formatted)?;
+        }
+    }
+
+    if num_unformatted > 0 {
+        Err(AppError::invalid(format!("{} file(s) need reformatting", num_unformatted)))
+    } else {
+        Ok(())
+    }
+}
+
+/// Name of the structure a bare rule expression entered in the REPL
+/// is compiled as.
+const REPL_INPUT_NAME: &str = "__input";
+
+const REPL_HELP: &str = "\
+Enter a structure or template definition, a context block, or a rule
+expression.  Input continues on the next line while braces or
+parentheses are left open.
+
+    :load FILE     add all definitions and blocks of FILE to the session
+    :root [NAME]   compile NAME after each input, or show the current root
+    :reset         forget all definitions, blocks and dots
+    :help          show this message
+    :quit          leave the REPL";
+
+/// State of an interactive session: definitions and context blocks
+/// entered so far, all compiled in a single context, which outlives
+/// individual inputs.
+struct Session {
+    ctx:       ContextHandle,
+    /// Source of each definition or block, keyed by name of defined
+    /// structure or template, so that redefinitions replace it.
+    blocks:    Vec<(Option<String>, String)>,
+    root_name: Option<String>,
+}
+
+impl Session {
+    fn new() -> Self {
+        Session { ctx: Context::new_toplevel("repl"), blocks: Vec::new(), root_name: None }
+    }
+
+    fn add_block(&mut self, name: Option<String>, source: String) {
+        if name.is_some() {
+            if let Some(entry) = self.blocks.iter_mut().find(|(key, _)| *key == name) {
+                entry.1 = source;
+                return
+            }
+        }
+
+        self.blocks.push((name, source));
+    }
+
+    /// Adds a definition or block to this session, replacing the
+    /// previous definition of the same name, if any.  If `root_name`
+    /// is given, the structure of that name is compiled and printed,
+    /// and the block is rejected if compilation fails.
+    fn try_block(
+        &mut self,
+        name: Option<String>,
+        source: &str,
+        root_name: Option<&str>,
+    ) -> Result<(), AppError> {
+        let saved = self.blocks.clone();
+
+        self.add_block(name, source.to_owned());
+
+        if let Some(root_name) = root_name {
+            if let Err(err) = self.compile_and_print(None, root_name) {
+                self.blocks = saved;
+
+                return Err(err)
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compiles all blocks of this session, followed by `extra`
+    /// source, and returns the result of compiling structure
+    /// `root_name`.
+    fn compile(&self, extra: Option<&str>, root_name: &str) -> Result<CesFile, AppError> {
+        let mut script: String =
+            self.blocks.iter().map(|(_, source)| format!("{}\n", source)).collect();
+
+        if let Some(extra) = extra {
+            script.push_str(extra);
+            script.push('\n');
+        }
+
+        let in_script = |err| AppError::in_script(err, Some("<repl>"));
+        let mut ces_file = CesFile::from_script(script).map_err(in_script)?;
+
+        ces_file.set_root_name(root_name).map_err(in_script)?;
+        ces_file.compile_mut(&self.ctx).map_err(in_script)?;
+
+        Ok(ces_file)
+    }
+
+    /// Prints the causes and effects of each dot of a compiled
+    /// structure, as thin arrow rules.
+    fn print_polynomials(&self, ces_file: &mut CesFile) {
+        let dot_ids = ces_file.get_carrier_ids();
+        let ctx = self.ctx.lock().unwrap();
+        let fmt_poly = |poly: Option<&Vec<Vec<DotId>>>| {
+            let monomials: Vec<_> = poly
+                .into_iter()
+                .flatten()
+                .map(|mono| {
+                    let names: Vec<_> =
+                        mono.iter().map(|&id| ctx.get_dot_name(id).unwrap_or("?")).collect();
+                    names.join(" ")
+                })
+                .collect();
+            monomials.join(" + ")
+        };
+
+        for id in dot_ids {
+            let name = ctx.get_dot_name(id).unwrap_or("?");
+            let causes = fmt_poly(ces_file.get_causes_by_id(id));
+            let effects = fmt_poly(ces_file.get_effects_by_id(id));
+
+            match (causes.is_empty(), effects.is_empty()) {
+                (true, true) => println!("    {}", name),
+                (true, false) => println!("    {} -> {}", name, effects),
+                (false, true) => println!("    {} <- {}", name, causes),
+                (false, false) => println!("    {} -> {} -> {}", causes, name, effects),
+            }
+        }
+    }
+
+    fn compile_and_print(&self, extra: Option<&str>, root_name: &str) -> Result<(), AppError> {
+        let mut ces_file = self.compile(extra, root_name)?;
+
+        self.print_polynomials(&mut ces_file);
+
+        Ok(())
+    }
+
+    fn run_command(&mut self, command: &str) -> Result<bool, AppError> {
+        let mut words = command.split_whitespace();
+
+        match (words.next(), words.next()) {
+            (Some(":quit"), None) | (Some(":q"), None) => return Ok(false),
+            (Some(":help"), None) | (Some(":h"), None) => println!("{}", REPL_HELP),
+            (Some(":reset"), None) => *self = Session::new(),
+            (Some(":load"), Some(path)) => {
+                let ces_file =
+                    CesFile::from_path(path).map_err(|err| AppError::in_script(err, Some(path)))?;
+
+                for block in ces_file.get_blocks() {
+                    let name = match block {
+                        CesFileBlock::Imm(imm) => Some(imm.get_name().to_string()),
+                        CesFileBlock::Template(template) => Some(template.get_name().to_string()),
+                        _ => None,
+                    };
+
+                    self.add_block(name, block.to_string());
+                }
+                println!("Loaded {} block(s) from {}", ces_file.get_blocks().len(), path);
+
+                if let Some(ref root_name) = self.root_name {
+                    self.compile_and_print(None, root_name)?;
+                }
+            }
+            (Some(":root"), Some(name)) => {
+                self.compile_and_print(None, name)?;
+                self.root_name = Some(name.to_owned());
+            }
+            (Some(":root"), None) => match self.root_name {
+                Some(ref name) => println!("Root: {}", name),
+                None => println!("Root unset"),
+            },
+            _ => {
+                return Err(AppError::failure(format!(
+                    "Unknown command '{}', try :help",
+                    command.trim()
+                )))
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn run_phrase(&mut self, phrase: &str) -> Result<(), AppError> {
+        let axiom = Axiom::guess_from_phrase(phrase);
+
+        println!("Axiom: {}", axiom.symbol());
+
+        let ast = axiom.parse(phrase).map_err(|err| AppError::in_script(err.into(), None))?;
+
+        println!("AST: {:?}", ast);
+
+        match axiom.symbol() {
+            "ImmediateDef" => {
+                let imm: ImmediateDef = phrase.parse().map_err(AppError::invalid)?;
+                let name = imm.get_name().to_string();
+
+                println!(
+                    "FIT: {}",
+                    ImmediateDef::new(imm.get_name().clone(), imm.get_rex().fit_clone())
+                );
+                self.try_block(Some(name.clone()), phrase, Some(&name))?;
+            }
+            "TemplateDef" => {
+                let template: TemplateDef = phrase.parse().map_err(AppError::invalid)?;
+                let fit = TemplateDef::new(
+                    template.get_name().clone(),
+                    template.get_args().to_vec(),
+                    template.get_rex().fit_clone(),
+                )
+                .map_err(AppError::invalid)?;
+
+                println!("FIT: {}", fit);
+
+                let root_name = self.root_name.clone();
+
+                self.try_block(
+                    Some(template.get_name().to_string()),
+                    phrase,
+                    root_name.as_deref(),
+                )?;
+            }
+            "Rex" | "ThinArrowRule" | "FatArrowRule" | "CesImmediate" | "CesInstance" => {
+                let rex: Rex = phrase.parse().map_err(AppError::invalid)?;
+
+                println!("FIT: {}", rex.fit_clone());
+
+                let source = format!("ces {} {{ {} }}", REPL_INPUT_NAME, phrase);
+
+                self.compile_and_print(Some(&source), REPL_INPUT_NAME)?;
+            }
+            "Polynomial" => {}
+            _ => {
+                // A context block, which affects the compilation of
+                // the root structure, if there is one.
+                let root_name = self.root_name.clone();
+
+                self.try_block(None, phrase, root_name.as_deref())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns `true` if all braces, parentheses and brackets opened in
+/// `input` are closed.
+///
+/// Comments and quoted names are single tokens, so brackets within
+/// them aren't counted.  Input which fails lexing, e.g. because of an
+/// unterminated quote, is complete as well, so that the error is
+/// reported instead of waiting for more input.
+fn is_complete_input(input: &str) -> bool {
+    let mut depth = 0;
+
+    for result in Lexer::new(input) {
+        match result {
+            Ok((_, Token::OpenCurly, _))
+            | Ok((_, Token::OpenParen, _))
+            | Ok((_, Token::OpenBracket, _)) => depth += 1,
+            Ok((_, Token::CloseCurly, _))
+            | Ok((_, Token::CloseParen, _))
+            | Ok((_, Token::CloseBracket, _)) => depth -= 1,
+            Ok(_) => {}
+            Err(_) => return true,
+        }
+    }
+
+    depth <= 0
+}
+
+fn cmd_repl(args: &clap::ArgMatches) -> Result<(), AppError> {
+    let mut session = Session::new();
+
+    if let Some(path) = args.value_of("FILE") {
+        session.run_command(&format!(":load {}", path))?;
+    }
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut input = String::new();
+
+    loop {
+        print!("{}", if input.is_empty() { "ces> " } else { "...> " });
+        io::stdout().flush()?;
+
+        match lines.next() {
+            Some(line) => {
+                input.push_str(&line?);
+                input.push('\n');
+            }
+            None => break,
+        }
+
+        if !is_complete_input(&input) {
+            continue
+        }
+
+        let phrase = std::mem::take(&mut input);
+        let phrase = phrase.trim();
+
+        let result = if phrase.is_empty() {
+            Ok(())
+        } else if phrase.starts_with(':') {
+            match session.run_command(phrase) {
+                Ok(true) => Ok(()),
+                Ok(false) => break,
+                Err(err) => Err(err),
+            }
+        } else {
+            session.run_phrase(phrase)
+        };
+
+        if let Err(err) = result {
+            err.report(ErrorFormat::Human);
+        }
+    }
+
+    Ok(())
+}
+
+fn setup_logger(verbosity: u64) {
+    let log_level = match verbosity {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+
+    let colors = ColoredLevelConfig::new()
+        .trace(Color::Blue)
+        .debug(Color::Yellow)
+        .info(Color::Green)
+        .warn(Color::Magenta)
+        .error(Color::Red);
+
+    let console_logger = fern::Dispatch::new()
+        .format(move |out, message, record| match record.level() {
+            log::Level::Info => out.finish(format_args!("{}.", message)),
+            log::Level::Warn | log::Level::Debug => {
+                out.finish(format_args!("[{}]\t{}.", colors.color(record.level()), message))
+            }
+            _ => out.finish(format_args!(
+                "[{}]\t\x1B[{}m{}.\x1B[0m",
+                colors.color(record.level()),
+                colors.get_color(&record.level()).to_fg_str(),
+                message
+            )),
+        })
+        .level(log_level)
+        .chain(std::io::stderr());
+
+    let root_logger = fern::Dispatch::new().chain(console_logger);
+    root_logger.apply().unwrap_or_else(|err| eprintln!("[ERROR] {}.", err));
+}
+
+fn main() {
+    let root_arg = clap::Arg::from_usage("-r, --root=[NAME] 'name of the root structure'")
+        .default_value("Main");
+
+    let app = clap::App::new("ascesis")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
+        .about("Ascesis language tools")
+        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+        .args_from_usage("-v, --verbose... 'level of verbosity'")
+        .arg(
+            clap::Arg::from_usage("--error-format=[FORMAT] 'format of error messages'")
+                .default_value("human")
+                .possible_values(&["human", "json"]),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("parse")
+                .about("Parses a script and prints its blocks in canonical form")
+                .args_from_usage(
+                    "<FILE>   'script to parse, or - for the standard input'
+                     --ast    'dump the syntax tree of each block instead'",
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("check")
+                .about("Parses and compiles a script")
+                .args_from_usage(
+                    "<FILE>                'script to check, or - for the standard input'
+                     -D, --deny-warnings   'fail, if any warnings are found'
+                     --coherence           'warn about links specified on one side only'",
+                )
+                .arg(root_arg.clone()),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("fit")
+                .about("Prints structures of a script in the thin arrow normal form")
+                .args_from_usage(
+                    "<FILE>              'script to transform, or - for the standard input'
+                     -r, --root=[NAME]   'name of the single structure to print'",
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("export")
+                .about("Converts a script to another format")
+                .args_from_usage(
+                    "<FILE>                'script to convert, or - for the standard input'
+                     -o, --output=[PATH]   'output file (default: standard output)'",
+                )
+                .arg(
+                    clap::Arg::from_usage("-f, --format=[FORMAT] 'output format'")
+                        .default_value("dot")
+                        .possible_values(&["ces", "dot"]),
+                )
+                .arg(root_arg.clone()),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("equiv")
+                .about("Checks whether two structures have the same causes and effects")
+                .args_from_usage(
+                    "<LEFT>                   'first script, or rule expression, given --rex'
+                     <RIGHT>                  'second script, or rule expression, given --rex'
+                     -e, --rex                'compare rule expressions instead of scripts'
+                     --other-root=[NAME]      'name of the root structure of the second script \
+                     (default: same as --root)'",
+                )
+                .arg(root_arg),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("generate")
+                .about("Generates random phrases of the Ascesis grammar")
+                .arg(
+                    clap::Arg::from_usage("[AXIOM] 'grammar symbol to start from'")
+                        .default_value("Rex"),
+                )
+                .arg(
+                    clap::Arg::from_usage("-n, --count=[COUNT] 'number of phrases'")
+                        .default_value("1"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("fmt").about("Formats scripts in place").args_from_usage(
+                "<FILE>...   'scripts to format, or - for the standard input'
+                     --check     'report files needing reformatting, instead of writing'",
+            ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("repl")
+                .about("Starts an interactive session")
+                .args_from_usage("[FILE] 'script to load at start'"),
+        );
+
+    let args = app.get_matches_safe().unwrap_or_else(|err| match err.kind {
+        clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => err.exit(),
+        _ => {
+            eprintln!("{}", err.message);
+            std::process::exit(EXIT_FAILURE)
+        }
+    });
+
+    setup_logger(args.occurrences_of("verbose"));
+
+    let error_format = match args.value_of("error-format") {
+        Some("json") => ErrorFormat::Json,
+        _ => ErrorFormat::Human,
+    };
+
+    let result = match args.subcommand() {
+        ("parse", Some(sub_args)) => cmd_parse(sub_args),
+        ("check", Some(sub_args)) => cmd_check(sub_args, error_format),
+        ("fit", Some(sub_args)) => cmd_fit(sub_args),
+        ("export", Some(sub_args)) => cmd_export(sub_args),
+        ("equiv", Some(sub_args)) => cmd_equiv(sub_args),
+        ("generate", Some(sub_args)) => cmd_generate(sub_args),
+        ("fmt", Some(sub_args)) => cmd_fmt(sub_args),
+        ("repl", Some(sub_args)) => cmd_repl(sub_args),
+        _ => unreachable!(),
+    };
+
+    if let Err(err) = result {
+        err.report(error_format);
+        std::process::exit(err.code);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_input() {
+        assert!(is_complete_input("ces Main { a => b }\n"));
+        assert!(!is_complete_input("ces Main {\n"));
+        assert!(!is_complete_input("ces Main { Fork!(a,\n"));
+        assert!(is_complete_input("ces Main {\n  a => b\n}\n"));
+
+        // Brackets in comments and quoted names aren't counted.
+        assert!(is_complete_input("vis { labels: { a: \"{\" } }\n"));
+        assert!(is_complete_input("a => b // {\n"));
+        assert!(!is_complete_input("ces Main { // }\n"));
+
+        // Unterminated quote.
+        assert!(is_complete_input("vis { title: \"{ }\n"));
+    }
+
+    #[test]
+    fn test_session_commands() {
+        let mut session = Session::new();
+
+        assert_eq!(session.run_command(":help").ok(), Some(true));
+        assert_eq!(session.run_command(":root").ok(), Some(true));
+        assert_eq!(session.run_command(":what").map_err(|err| err.code).err(), Some(EXIT_FAILURE));
+        assert!(session.run_command(":load missing.ces").is_err());
+
+        session.run_phrase("ces Main { a => b }").unwrap();
+        session.run_phrase("caps { 2 a }").unwrap();
+        assert_eq!(session.blocks.len(), 2);
+
+        // A root which doesn't compile is rejected.
+        assert!(session.run_command(":root Missing").is_err());
+        assert_eq!(session.root_name, None);
+
+        assert_eq!(session.run_command(":root Main").ok(), Some(true));
+        assert_eq!(session.root_name.as_deref(), Some("Main"));
+
+        assert_eq!(session.run_command(":reset").ok(), Some(true));
+        assert!(session.blocks.is_empty() && session.root_name.is_none());
+
+        assert_eq!(session.run_command(":quit").ok(), Some(false));
+    }
+}
@@ -1,12 +1,11 @@
-use std::{collections::BTreeSet, iter::FromIterator};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    iter::FromIterator,
+    ops::{Add, AddAssign, Mul, MulAssign},
+    fmt,
+};
 use aces::{ContextHandle, DotId};
-use crate::{DotName, ToDotName, DotList};
-
-#[derive(Clone, PartialEq, Eq, Debug)]
-pub(crate) enum Warning {
-    SumIdempotency(BTreeSet<DotName>),
-    ProductIdempotency(DotName),
-}
+use crate::{DotName, ToDotName, DotList, Span, AscesisWarning, AscesisWarningKind, span::NodeSpan};
 
 /// An alphabetically ordered and deduplicated list of monomials,
 /// where each monomial is alphabetically ordered and deduplicated
@@ -17,16 +16,42 @@ pub(crate) enum Warning {
 /// the `Polynomial` originated from was syntactically valid as a dot
 /// list, or if the `Polynomial` is the result of
 /// [`Polynomial::flattened_clone`].
-#[derive(Clone, PartialEq, Eq, Debug)]
+///
+/// Polynomials are added and multiplied with the `+` and `*`
+/// operators, and their assigning forms.  Both operations are
+/// idempotent: a monomial added to a `Polynomial` containing it, or a
+/// dot multiplied by a monomial containing it, leaves the result
+/// unchanged, but is recorded as a warning.
+///
+/// Two polynomials are equal if they consist of the same monomials.
+/// The `is_flat` flag and warnings aren't compared, so that a
+/// `Polynomial` equals the result of parsing its textual form.
+#[derive(Clone, Debug)]
 pub struct Polynomial {
     pub(crate) monomials: BTreeSet<BTreeSet<DotName>>,
 
     // FIXME falsify on leading "+" or parens, even if still a single mono
     pub(crate) is_flat:  bool,
-    pub(crate) warnings: Vec<Warning>,
+    pub(crate) warnings: Vec<AscesisWarning>,
+    pub(crate) span:     NodeSpan,
 }
 
 impl Polynomial {
+    /// Sets the span of this `Polynomial`, which also becomes the span
+    /// of all warnings raised while building it, unless a warning was
+    /// raised inside a parenthesized subexpression with a span of its
+    /// own.
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span.into();
+        self.warnings = self.warnings.into_iter().map(|w| w.with_default_span(span)).collect();
+        self
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        *self.span
+    }
+
     /// Returns `self` multiplied by the product of `factors`.
     pub(crate) fn with_product_multiplied(mut self, mut factors: Vec<Self>) -> Self {
         self.multiply_assign(&mut factors);
@@ -44,33 +69,42 @@ impl Polynomial {
 
     /// Transform this `Polynomial` into a [`DotList`]-compatible form
     /// by gathering all [`DotName`]s as a single-monomial
-    /// `Polynomial` with the `is_flat` flag set.
-    pub(crate) fn flattened_clone(&self) -> Self {
+    /// `Polynomial` with the `is_flat` flag set.  An empty
+    /// `Polynomial` is flattened into an empty one.
+    pub fn flattened_clone(&self) -> Self {
         if self.is_flat {
             self.clone()
         } else {
             let warnings = self.warnings.clone();
             let mut more_monos = self.monomials.iter();
-            let mut single_mono = more_monos.next().expect("non-flat empty polynomial").clone();
+            let single_mono = more_monos.next().map(|mono| {
+                let mut single_mono = mono.clone();
 
-            for mono in more_monos {
-                single_mono.append(&mut mono.clone());
-            }
+                for mono in more_monos {
+                    single_mono.append(&mut mono.clone());
+                }
+                single_mono
+            });
 
             Polynomial {
-                monomials: BTreeSet::from_iter(Some(single_mono)),
+                monomials: BTreeSet::from_iter(single_mono),
                 is_flat: true,
                 warnings,
+                span: self.span,
             }
         }
     }
 
+    /// Multiplies this `Polynomial` by all `factors`, in order, taking
+    /// their warnings.
     pub(crate) fn multiply_assign(&mut self, factors: &mut [Self]) {
         for factor in factors {
             if !factor.is_flat {
                 self.is_flat = false;
             }
 
+            self.warnings.append(&mut factor.warnings);
+
             let lhs: Vec<_> = self.monomials.iter().cloned().collect();
             self.monomials.clear();
 
@@ -78,7 +112,8 @@ impl Polynomial {
                 for other_mono in factor.monomials.iter() {
                     if !this_mono.is_disjoint(other_mono) {
                         for dot in this_mono.intersection(&other_mono) {
-                            self.warnings.push(Warning::ProductIdempotency(dot.clone()));
+                            self.warnings
+                                .push(AscesisWarningKind::ProductIdempotency(dot.clone()).into());
                         }
                     }
 
@@ -88,20 +123,115 @@ impl Polynomial {
                 }
             }
         }
-        self.log_warnings();
     }
 
+    /// Adds `other` to this `Polynomial`, leaving `other` empty.
     pub(crate) fn add_assign(&mut self, other: &mut Self) {
         self.is_flat = false;
 
         if !self.monomials.is_disjoint(&other.monomials) {
             for mono in self.monomials.intersection(&other.monomials) {
-                self.warnings.push(Warning::SumIdempotency(mono.clone()));
+                let dots = mono.iter().cloned().collect();
+
+                self.warnings.push(AscesisWarningKind::SumIdempotency(dots).into());
             }
         }
 
+        self.warnings.append(&mut other.warnings);
         self.monomials.append(&mut other.monomials);
-        self.log_warnings();
+    }
+
+    /// Replaces [`DotName`]s found in `dot_map` keys with the
+    /// corresponding values.  Monomials which become equal after
+    /// renaming are merged, and so are dots within a monomial.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) {
+        let monomials = std::mem::take(&mut self.monomials);
+
+        self.monomials = monomials
+            .into_iter()
+            .map(|mono| {
+                mono.into_iter().map(|dot| dot_map.get(&dot).cloned().unwrap_or(dot)).collect()
+            })
+            .collect();
+
+        if self.monomials.len() < 2 {
+            self.is_flat = true;
+        }
+    }
+
+    /// Drops warnings and sets the `is_flat` flag if this
+    /// `Polynomial` has at most one monomial, so that polynomials
+    /// with equal monomials compare equal, however they were built.
+    pub(crate) fn normalize(&mut self) {
+        self.is_flat = self.monomials.len() < 2;
+        self.warnings.clear();
+    }
+
+    /// Returns a copy of this `Polynomial`, where `dot_name` is
+    /// replaced with `poly` in every monomial, i.e. each monomial
+    /// containing `dot_name` is divided by it and multiplied by `poly`.
+    /// Substitution of an empty `poly` removes all such monomials.
+    pub fn substitute(&self, dot_name: &DotName, poly: &Polynomial) -> Self {
+        let mut monomials = BTreeSet::new();
+
+        for mono in self.monomials.iter() {
+            if mono.contains(dot_name) {
+                for other_mono in poly.monomials.iter() {
+                    let mut new_mono = mono.clone();
+
+                    new_mono.remove(dot_name);
+                    new_mono.extend(other_mono.iter().cloned());
+                    monomials.insert(new_mono);
+                }
+            } else {
+                monomials.insert(mono.clone());
+            }
+        }
+
+        let mut result = Polynomial { monomials, span: self.span, ..Default::default() };
+
+        result.normalize();
+        result
+    }
+
+    /// Returns `true` if this `Polynomial` has no monomials, i.e. if
+    /// it is _&theta;_.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.monomials.is_empty()
+    }
+
+    /// Returns the number of monomials of this `Polynomial`.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.monomials.len()
+    }
+
+    /// Returns an iterator over all monomials of this `Polynomial`, in
+    /// their canonical order.
+    pub fn get_monomials(&self) -> impl Iterator<Item = &BTreeSet<DotName>> {
+        self.monomials.iter()
+    }
+
+    /// Returns the largest number of dots in a monomial of this
+    /// `Polynomial`, or zero, if it is empty.
+    pub fn get_degree(&self) -> usize {
+        self.monomials.iter().map(BTreeSet::len).max().unwrap_or(0)
+    }
+
+    /// Returns the set of all dots occurring in this `Polynomial`.
+    pub fn get_support(&self) -> BTreeSet<&DotName> {
+        self.get_dot_names().collect()
+    }
+
+    pub fn contains_dot(&self, dot_name: &DotName) -> bool {
+        self.monomials.iter().any(|mono| mono.contains(dot_name))
+    }
+
+    /// Returns an iterator over dots of all monomials of this
+    /// `Polynomial`, in order, hence with repetitions.
+    pub fn get_dot_names(&self) -> impl Iterator<Item = &DotName> {
+        self.monomials.iter().flatten()
     }
 
     pub(crate) fn compile_as_vec(&self, ctx: &ContextHandle) -> Vec<Vec<DotId>> {
@@ -113,23 +243,90 @@ impl Polynomial {
             .collect()
     }
 
-    pub fn log_warnings(&self) {
-        for warning in self.warnings.iter() {
-            match warning {
-                Warning::SumIdempotency(dots) => {
-                    warn!("Applying sum idempotency of {:?}", dots);
-                }
-                Warning::ProductIdempotency(dot) => {
-                    warn!("Applying product idempotency of {:?}", dot);
-                }
-            }
-        }
+    /// Returns warnings raised while building this `Polynomial`,
+    /// i.e. applications of sum and product idempotency laws.
+    #[inline]
+    pub fn get_warnings(&self) -> &[AscesisWarning] {
+        self.warnings.as_slice()
     }
 }
 
 impl Default for Polynomial {
     fn default() -> Self {
-        Polynomial { monomials: BTreeSet::default(), is_flat: true, warnings: Vec::new() }
+        Polynomial {
+            monomials: BTreeSet::default(),
+            is_flat:   true,
+            warnings:  Vec::new(),
+            span:      Default::default(),
+        }
+    }
+}
+
+impl PartialEq for Polynomial {
+    fn eq(&self, other: &Self) -> bool {
+        self.monomials == other.monomials
+    }
+}
+
+impl Eq for Polynomial {}
+
+impl AddAssign for Polynomial {
+    fn add_assign(&mut self, mut other: Self) {
+        Polynomial::add_assign(self, &mut other);
+    }
+}
+
+impl Add for Polynomial {
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self {
+        self += other;
+        self
+    }
+}
+
+impl Add for &Polynomial {
+    type Output = Polynomial;
+
+    fn add(self, other: Self) -> Polynomial {
+        self.clone() + other.clone()
+    }
+}
+
+impl MulAssign for Polynomial {
+    fn mul_assign(&mut self, other: Self) {
+        self.multiply_assign(&mut [other]);
+    }
+}
+
+impl Mul for Polynomial {
+    type Output = Self;
+
+    fn mul(mut self, other: Self) -> Self {
+        self *= other;
+        self
+    }
+}
+
+impl Mul for &Polynomial {
+    type Output = Polynomial;
+
+    fn mul(self, other: Self) -> Polynomial {
+        self.clone() * other.clone()
+    }
+}
+
+/// Prints a `Polynomial` as a sum of monomials, in their canonical
+/// order.  An empty `Polynomial` is printed as an empty string.
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let monomials: Vec<_> = self
+            .monomials
+            .iter()
+            .map(|mono| mono.iter().map(|n| n.as_ref()).collect::<Vec<_>>().join(" "))
+            .collect();
+
+        write!(f, "{}", monomials.join(" + "))
     }
 }
 
@@ -252,5 +449,72 @@ mod tests {
                 ..Default::default()
             }
         );
+        assert!(!poly.is_flat);
+    }
+
+    #[test]
+    fn test_flattened_empty() {
+        let a = Polynomial::from("a".to_dot());
+        let b = Polynomial::from("b".to_dot());
+
+        for poly in
+            &[Polynomial::default() + Polynomial::default(), (a + b) * Polynomial::default()]
+        {
+            assert!(!poly.is_flat);
+
+            let flat = poly.flattened_clone();
+
+            assert_eq!(flat, Polynomial::default());
+            assert!(flat.is_flat);
+        }
+    }
+
+    #[test]
+    fn test_warnings() {
+        let phrase = "a + (b c b) + a";
+        let poly: Polynomial = phrase.parse().unwrap();
+        let warnings: Vec<_> = poly
+            .get_warnings()
+            .iter()
+            .map(|w| {
+                let span = w.get_span().unwrap();
+                (w.get_kind().clone(), span.start(), span.end())
+            })
+            .collect();
+
+        assert_eq!(
+            warnings,
+            vec![
+                (AscesisWarningKind::ProductIdempotency("b".to_dot()), 5, 10),
+                (AscesisWarningKind::SumIdempotency(vec!["a".to_dot()]), 0, 15),
+            ]
+        );
+
+        let printed = poly.to_string();
+
+        assert_eq!(printed, "a + b c");
+        assert_eq!(printed.parse::<Polynomial>().unwrap(), poly);
+    }
+
+    #[test]
+    fn test_algebra() {
+        let a = Polynomial::from("a");
+        let bc = Polynomial::from(vec![vec!["b"], vec!["c"]]);
+        let poly = &a * &bc + Polynomial::from("d");
+
+        assert_eq!(poly.to_string(), "a b + a c + d");
+        assert_eq!(poly.len(), 3);
+        assert_eq!(poly.get_degree(), 2);
+        assert_eq!(poly.get_support().len(), 4);
+        assert_eq!(poly.get_monomials().next().unwrap().len(), 2);
+
+        let poly = poly.substitute(&"a".to_dot(), &Polynomial::from(vec![vec!["c"], vec!["e"]]));
+
+        assert_eq!(poly.to_string(), "b c + b e + c + c e + d");
+        assert_eq!(poly, "b c + b e + c + c e + d".parse().unwrap());
+        assert_eq!(
+            poly.substitute(&"b".to_dot(), &Polynomial::default()).to_string(),
+            "c + c e + d"
+        );
     }
 }
@@ -1,16 +1,22 @@
-use std::{convert::TryInto, error::Error};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryInto,
+    fmt,
+    error::Error,
+};
 use log::Level::Debug;
-use aces::{ContextHandle, PartialContent, CompilableAsContent};
+use aces::{ContextHandle, PartialContent, CompilableAsContent, Polarity};
 use crate::{
-    CesImmediate, CesInstance, DotName, DotList, BinOp, polynomial::Polynomial, AscesisError,
-    AscesisErrorKind,
+    CesName, ToCesName, CesImmediate, CesInstance, ArgType, ArgValue, DotName, DotList, Span,
+    BinOp, polynomial::Polynomial, AscesisError, AscesisErrorKind, AscesisWarning, span::NodeSpan,
 };
 
 pub(crate) type RexID = usize;
 
 #[derive(Clone, PartialEq, Eq, Default, Debug)]
 pub(crate) struct RexTree {
-    ids: Vec<RexID>,
+    ids:  Vec<RexID>,
+    span: NodeSpan,
 }
 
 impl RexTree {
@@ -48,7 +54,7 @@ impl Rex {
                 offset = kinds.append_with_offset(rex.kinds, offset);
             }
 
-            kinds[0] = RexKind::Product(RexTree { ids });
+            kinds[0] = RexKind::Product(RexTree { ids, ..Default::default() });
 
             Rex { kinds }
         } else {
@@ -107,81 +113,361 @@ impl Rex {
             }
 
             if !product_ids.is_empty() {
-                kinds[anchor] = RexKind::Product(RexTree { ids: product_ids });
+                kinds[anchor] =
+                    RexKind::Product(RexTree { ids: product_ids, ..Default::default() });
             }
             sum_ids.push(anchor);
-            kinds[0] = RexKind::Sum(RexTree { ids: sum_ids });
+            kinds[0] = RexKind::Sum(RexTree { ids: sum_ids, ..Default::default() });
 
             Rex { kinds }
         }
     }
 
-    /// Returns a copy of this `Rex` converted to the normal form.
-    // FIXME the result of FIT transformation should be further
-    // simplified.
-    pub fn fit_clone(&self) -> Self {
-        let mut new_kinds = Vec::new();
-        let mut id_map = Vec::new();
-
-        for old_kind in self.kinds.iter() {
-            id_map.push(new_kinds.len());
+    /// Sets the span of the root of this `Rex`, unless the root is a
+    /// single arrow rule or instantiation, which has a span of its own.
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        if let Some(RexKind::Product(tree)) | Some(RexKind::Sum(tree)) = self.kinds.first_mut() {
+            tree.span = span.into();
+        }
+        self
+    }
 
-            if let RexKind::Fat(far) = old_kind {
-                let tars: Vec<ThinArrowRule> = far.into();
-                let ids: Vec<RexID> = std::iter::repeat(0).take(tars.len()).collect();
+    /// Returns the span of the root of this `Rex`, or an empty span if
+    /// this `Rex` is empty.
+    pub fn get_span(&self) -> Span {
+        self.kinds.first().map(RexKind::get_span).unwrap_or_default()
+    }
 
-                new_kinds.push(RexKind::Sum(RexTree { ids }));
-                new_kinds.extend(tars.into_iter().map(RexKind::Thin));
+    /// Returns an iterator over all template instantiations occurring
+    /// in this `Rex`.
+    pub(crate) fn get_instances(&self) -> impl Iterator<Item = &CesInstance> {
+        self.kinds.iter().filter_map(|kind| {
+            if let RexKind::Instance(instance) = kind {
+                Some(instance)
             } else {
-                new_kinds.push(old_kind.clone());
+                None
+            }
+        })
+    }
+
+    /// Replaces [`DotName`]s found in `dot_map` keys with the
+    /// corresponding values, in all rules of this `Rex` and in
+    /// arguments of its instantiations.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) {
+        self.rename_rule_dots(dot_map);
+
+        for kind in self.kinds.iter_mut() {
+            if let RexKind::Instance(instance) = kind {
+                instance.rename_dots(dot_map);
             }
         }
+    }
 
-        for (mut ndx, new_kind) in new_kinds.iter_mut().enumerate() {
-            match new_kind {
-                RexKind::Product(tree) | RexKind::Sum(tree) => {
-                    if let Some(first) = tree.ids.first() {
-                        if *first > 0 {
-                            for id in tree.ids.iter_mut() {
-                                assert!(*id > 0);
-                                *id = id_map[*id];
-                            }
-                        } else {
-                            for id in tree.ids.iter_mut() {
-                                assert_eq!(*id, 0);
-                                ndx += 1;
-                                *id = ndx;
+    /// Replaces [`DotName`]s found in `dot_map` keys with the
+    /// corresponding values, in all rules of this `Rex`, leaving
+    /// arguments of its instantiations unchanged.
+    pub(crate) fn rename_rule_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) {
+        for kind in self.kinds.iter_mut() {
+            match kind {
+                RexKind::Thin(tar) => tar.rename_dots(dot_map),
+                RexKind::Fat(far) => far.rename_dots(dot_map),
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns names of all structures this `Rex` depends on, each
+    /// paired with the span of the instantiation referencing it.
+    ///
+    /// A template instantiation is a dependency on the structure
+    /// named after the instantiation.
+    pub(crate) fn get_dependencies(&self) -> Vec<(CesName, Span)> {
+        self.kinds
+            .iter()
+            .filter_map(|kind| match kind {
+                RexKind::Immediate(immediate) => Some((immediate.name.clone(), *immediate.span)),
+                RexKind::Instance(instance) => Some((instance.get_instance_name(), *instance.span)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Replaces names of structures and templates referenced in this
+    /// `Rex` with names returned by `resolve_structure` and
+    /// `resolve_template`, respectively.
+    ///
+    /// Arguments of template instantiations are replaced as well, but
+    /// only those passed for parameters of type `CES` or `Hybrid`,
+    /// according to `arg_types`, which maps qualified template names
+    /// to parameter types.  Names passed for parameters of type `Dot`
+    /// are left alone, even if a structure of the same name is in
+    /// scope.
+    pub(crate) fn resolve_names<F, G>(
+        &mut self,
+        resolve_structure: F,
+        resolve_template: G,
+        arg_types: &BTreeMap<CesName, Vec<ArgType>>,
+    ) -> Result<(), AscesisErrorKind>
+    where
+        F: Fn(&CesName, Span) -> Result<Option<CesName>, AscesisErrorKind>,
+        G: Fn(&CesName, Span) -> Result<Option<CesName>, AscesisErrorKind>,
+    {
+        for kind in self.kinds.iter_mut() {
+            match kind {
+                RexKind::Immediate(immediate) => {
+                    if let Some(name) = resolve_structure(&immediate.name, *immediate.span)? {
+                        immediate.name = name;
+                    }
+                }
+                RexKind::Instance(instance) => {
+                    if let Some(name) = resolve_template(&instance.name, *instance.span)? {
+                        instance.name = name;
+                    }
+
+                    if let Some(types) = arg_types.get(&instance.name) {
+                        for (arg, arg_type) in instance.args.iter_mut().zip(types.iter()) {
+                            if let ArgValue::Identifier(identifier) = arg {
+                                if matches!(arg_type, ArgType::CES | ArgType::Hybrid) {
+                                    if let Some(name) = resolve_structure(
+                                        &identifier.to_ces_name(),
+                                        *instance.span,
+                                    )? {
+                                        *identifier = name.to_string();
+                                    }
+                                }
                             }
                         }
-                    } else {
-                        panic!()
                     }
                 }
                 _ => {}
             }
         }
 
-        Rex { kinds: new_kinds }
+        Ok(())
     }
-}
 
-impl CompilableAsContent for Rex {
-    fn check_dependencies(&self, ctx: &ContextHandle) -> Option<String> {
-        let ctx = ctx.lock().unwrap();
+    /// Replaces references to structures in immediate instantiations
+    /// according to `ces_map`.  Arguments of template instantiations
+    /// are left unchanged, see [`Rex::substitute_args()`].
+    pub(crate) fn rename_structures(&mut self, ces_map: &BTreeMap<CesName, CesName>) {
+        for kind in self.kinds.iter_mut() {
+            if let RexKind::Immediate(immediate) = kind {
+                immediate.rename_structures(ces_map);
+            }
+        }
+    }
 
-        for kind in self.kinds.iter() {
+    /// Replaces immediate instantiations of the structures named in
+    /// `ces_names` with empty thin arrow rules.
+    pub(crate) fn erase_structures(&mut self, ces_names: &BTreeSet<CesName>) {
+        for kind in self.kinds.iter_mut() {
             if let RexKind::Immediate(immediate) = kind {
-                if !ctx.has_content(&immediate.name) {
-                    return Some((*immediate.name).clone())
+                if ces_names.contains(&immediate.name) {
+                    *kind = RexKind::Thin(ThinArrowRule::new().with_span(*immediate.span));
                 }
-            } else if let RexKind::Instance(instance) = kind {
-                if !ctx.has_content(&instance.name) {
-                    return Some((*instance.name).clone())
+            }
+        }
+    }
+
+    /// Returns names of all dots occurring in arrow rules of this
+    /// `Rex`, and of all identifiers passed to template instantiations,
+    /// since these may name dots.
+    pub(crate) fn get_dot_names(&self) -> BTreeSet<&str> {
+        let mut dot_names = BTreeSet::new();
+
+        for kind in self.kinds.iter() {
+            match kind {
+                RexKind::Thin(tar) => dot_names.extend(tar.get_dot_names().map(|n| n.as_ref())),
+                RexKind::Fat(far) => dot_names.extend(far.get_dot_names().map(|n| n.as_ref())),
+                RexKind::Instance(instance) => {
+                    dot_names.extend(instance.args.iter().filter_map(|arg| match arg {
+                        ArgValue::Identifier(identifier) => Some(identifier.as_str()),
+                        _ => None,
+                    }))
                 }
+                _ => {}
             }
         }
 
-        None
+        dot_names
+    }
+
+    /// Returns `true` if `dot_name` occurs in any arrow rule of this
+    /// `Rex`.
+    pub(crate) fn contains_dot(&self, dot_name: &DotName) -> bool {
+        self.kinds.iter().any(|kind| match kind {
+            RexKind::Thin(tar) => tar.contains_dot(dot_name),
+            RexKind::Fat(far) => far.contains_dot(dot_name),
+            _ => false,
+        })
+    }
+
+    /// Returns an iterator over all thin arrow rules occurring in this
+    /// `Rex`.  Fat arrow rules aren't transformed.
+    pub(crate) fn get_thin_rules(&self) -> impl Iterator<Item = &ThinArrowRule> {
+        self.kinds.iter().filter_map(
+            |kind| {
+                if let RexKind::Thin(tar) = kind {
+                    Some(tar)
+                } else {
+                    None
+                }
+            },
+        )
+    }
+
+    /// Returns warnings raised while parsing arrow rules of this
+    /// `Rex`.
+    pub(crate) fn get_warnings(&self) -> Vec<AscesisWarning> {
+        let mut warnings = Vec::new();
+
+        for kind in self.kinds.iter() {
+            match kind {
+                RexKind::Thin(tar) => warnings.extend(tar.get_warnings()),
+                RexKind::Fat(far) => warnings.extend(far.get_warnings()),
+                _ => {}
+            }
+        }
+
+        warnings
+    }
+
+    /// Replaces arguments of template instantiations which name a
+    /// parameter of the enclosing template with the value bound to
+    /// that parameter in `args`.  Each argument is replaced at most
+    /// once, so that a value is never substituted again.
+    pub(crate) fn substitute_args(&mut self, args: &BTreeMap<String, ArgValue>) {
+        for kind in self.kinds.iter_mut() {
+            if let RexKind::Instance(instance) = kind {
+                instance.substitute_args(args);
+            }
+        }
+    }
+
+    /// Returns a copy of this `Rex` converted to the normal form.
+    ///
+    /// Fat arrow rules are replaced with sums of thin arrow rules,
+    /// nested sums and nested products are flattened, and thin arrow
+    /// rules of each sum are folded together.  Single-element sums
+    /// and products are replaced with their only element.  Finally,
+    /// elements of sums and products are put in canonical order, so
+    /// that equivalent expressions have equal normal forms.
+    pub fn fit_clone(&self) -> Self {
+        let mut kinds = Vec::new();
+
+        if !self.kinds.is_empty() {
+            self.fit_node(0).push_kinds(&mut kinds);
+        }
+
+        Rex { kinds }
+    }
+
+    fn fit_node(&self, pos: RexID) -> FitNode {
+        match &self.kinds[pos] {
+            RexKind::Thin(tar) => {
+                if tar.is_empty() {
+                    FitNode::Leaf(RexKind::Thin(tar.clone()))
+                } else {
+                    FitNode::sum(vec![FitNode::Leaf(RexKind::Thin(tar.clone()))], *tar.span)
+                }
+            }
+            RexKind::Fat(far) => {
+                let tars: Vec<ThinArrowRule> = far.into();
+
+                FitNode::sum(
+                    tars.into_iter().map(|tar| FitNode::Leaf(RexKind::Thin(tar))).collect(),
+                    *far.span,
+                )
+            }
+            RexKind::Product(tree) => {
+                FitNode::product(tree.ids.iter().map(|&id| self.fit_node(id)).collect(), *tree.span)
+            }
+            RexKind::Sum(tree) => {
+                FitNode::sum(tree.ids.iter().map(|&id| self.fit_node(id)).collect(), *tree.span)
+            }
+            kind => FitNode::Leaf(kind.clone()),
+        }
+    }
+}
+
+impl Rex {
+    fn fmt_kind(
+        &self,
+        pos: RexID,
+        parent: Option<&RexKind>,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        let kind = &self.kinds[pos];
+
+        match kind {
+            RexKind::Thin(tar) => {
+                if parent.is_some() && !tar.is_empty() {
+                    write!(f, "{{ {} }}", tar)
+                } else {
+                    write!(f, "{}", tar)
+                }
+            }
+            RexKind::Fat(far) => {
+                if parent.is_some() {
+                    write!(f, "{{ {} }}", far)
+                } else {
+                    write!(f, "{}", far)
+                }
+            }
+            RexKind::Immediate(immediate) => write!(f, "{}", immediate),
+            RexKind::Instance(instance) => write!(f, "{}", instance),
+            RexKind::Product(tree) | RexKind::Sum(tree) => {
+                let (separator, is_braced) = if let RexKind::Product(_) = kind {
+                    (" ", matches!(parent, Some(RexKind::Product(_))))
+                } else {
+                    (" + ", parent.is_some())
+                };
+
+                if is_braced {
+                    write!(f, "{{ ")?;
+                }
+
+                for (num, &id) in tree.ids.iter().enumerate() {
+                    if num > 0 {
+                        write!(f, "{}", separator)?;
+                    }
+                    self.fmt_kind(id, Some(kind), f)?;
+                }
+
+                if is_braced {
+                    write!(f, " }}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Prints a `Rex` in its textual form.
+///
+/// Braces are inserted only where needed for parsing the result back
+/// into an equal `Rex`, i.e. around arrow rules which aren't the
+/// whole `Rex`, around sums which are addends or factors, and around
+/// products which are factors.
+impl fmt::Display for Rex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.kinds.is_empty() {
+            Ok(())
+        } else {
+            self.fmt_kind(0, None, f)
+        }
+    }
+}
+
+impl CompilableAsContent for Rex {
+    fn check_dependencies(&self, ctx: &ContextHandle) -> Option<String> {
+        let ctx = ctx.lock().unwrap();
+
+        self.get_dependencies()
+            .into_iter()
+            .find(|(dep_name, _)| !ctx.has_content(dep_name))
+            .map(|(dep_name, _)| dep_name.to_string())
     }
 
     fn get_compiled_content(&self, ctx: &ContextHandle) -> Result<PartialContent, Box<dyn Error>> {
@@ -225,20 +511,21 @@ impl CompilableAsContent for Rex {
                         return Err(AscesisError::from(AscesisErrorKind::UnexpectedDependency(
                             (*immediate.name).clone(),
                         ))
+                        .with_span(*immediate.span)
                         .into())
                     }
                 }
                 RexKind::Instance(instance) => {
-                    // FIXME
-                    debug!("--> in rex, {}", instance.name);
+                    let instance_name = instance.get_instance_name();
                     let ctx = ctx.lock().unwrap();
 
-                    if let Some(content) = ctx.get_content(&instance.name) {
+                    if let Some(content) = ctx.get_content(&instance_name) {
                         content.clone()
                     } else {
                         return Err(AscesisError::from(AscesisErrorKind::UnexpectedDependency(
-                            (*instance.name).clone(),
+                            instance_name.to_string(),
                         ))
+                        .with_span(*instance.span)
                         .into())
                     }
                 }
@@ -310,6 +597,18 @@ pub(crate) enum RexKind {
     Sum(RexTree),
 }
 
+impl RexKind {
+    fn get_span(&self) -> Span {
+        match self {
+            RexKind::Thin(tar) => *tar.span,
+            RexKind::Fat(far) => *far.span,
+            RexKind::Immediate(immediate) => *immediate.span,
+            RexKind::Instance(instance) => *instance.span,
+            RexKind::Product(tree) | RexKind::Sum(tree) => *tree.span,
+        }
+    }
+}
+
 trait AppendWithOffset {
     fn append_with_offset(&mut self, source: Self, offset: usize) -> usize;
 }
@@ -330,23 +629,165 @@ impl AppendWithOffset for Vec<RexKind> {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Default, Debug)]
+type FitOrderKey = (u8, Option<(bool, bool, Vec<DotName>)>, String);
+
+/// A node of a `Rex` in the normal form, owning its elements.  Used
+/// only while building the normal form, see [`Rex::fit_clone`].
+enum FitNode {
+    Leaf(RexKind),
+    Product(Vec<FitNode>, Span),
+    Sum(Vec<FitNode>, Span),
+}
+
+impl FitNode {
+    fn product(factors: Vec<FitNode>, span: Span) -> Self {
+        let mut elements = Vec::new();
+
+        for factor in factors {
+            match factor {
+                FitNode::Product(more_factors, _) => elements.extend(more_factors),
+                factor => elements.push(factor),
+            }
+        }
+
+        Self::from_elements(elements, span, FitNode::Product)
+    }
+
+    fn sum(addends: Vec<FitNode>, span: Span) -> Self {
+        let mut tars = Vec::new();
+        let mut elements = Vec::new();
+
+        for addend in addends {
+            let more_addends = match addend {
+                FitNode::Sum(more_addends, _) => more_addends,
+                addend => vec![addend],
+            };
+
+            for addend in more_addends {
+                match addend {
+                    FitNode::Leaf(RexKind::Thin(tar)) => tars.push(tar),
+                    addend => elements.push(addend),
+                }
+            }
+        }
+
+        elements
+            .extend(fold_thin_rules(tars).into_iter().map(|tar| FitNode::Leaf(RexKind::Thin(tar))));
+
+        if elements.is_empty() {
+            FitNode::Leaf(RexKind::Thin(ThinArrowRule::new().with_span(span)))
+        } else {
+            Self::from_elements(elements, span, FitNode::Sum)
+        }
+    }
+
+    fn from_elements<F>(mut elements: Vec<FitNode>, span: Span, make_tree: F) -> Self
+    where
+        F: FnOnce(Vec<FitNode>, Span) -> Self,
+    {
+        if elements.len() == 1 {
+            elements.pop().unwrap()
+        } else {
+            elements.sort_by_cached_key(FitNode::get_order_key);
+            make_tree(elements, span)
+        }
+    }
+
+    /// Returns the key by which elements of sums and products are
+    /// ordered: thin arrow rules come first, in the order of
+    /// [`ThinArrowRule::get_order_key`], followed by immediate and
+    /// template instantiations, products and sums, each ordered by
+    /// their textual form.
+    fn get_order_key(&self) -> FitOrderKey {
+        match self {
+            FitNode::Leaf(RexKind::Thin(tar)) => {
+                let (is_rx, is_dx, dots) = tar.get_order_key();
+
+                (0, Some((is_rx, is_dx, dots.to_vec())), String::new())
+            }
+            FitNode::Leaf(RexKind::Immediate(immediate)) => (1, None, immediate.to_string()),
+            FitNode::Leaf(RexKind::Instance(instance)) => (2, None, instance.to_string()),
+            node => {
+                let mut kinds = Vec::new();
+
+                node.push_kinds(&mut kinds);
+
+                let rank = if let FitNode::Product(..) = node { 3 } else { 4 };
+
+                (rank, None, Rex { kinds }.to_string())
+            }
+        }
+    }
+
+    /// Appends this node and all its descendants to `kinds`, each
+    /// before its elements.
+    fn push_kinds(&self, kinds: &mut Vec<RexKind>) {
+        match self {
+            FitNode::Leaf(kind) => kinds.push(kind.clone()),
+            FitNode::Product(elements, span) | FitNode::Sum(elements, span) => {
+                let pos = kinds.len();
+                let mut ids = Vec::new();
+
+                kinds.push(RexKind::Product(RexTree::default()));
+
+                for element in elements {
+                    ids.push(kinds.len());
+                    element.push_kinds(kinds);
+                }
+
+                let tree = RexTree { ids, span: (*span).into() };
+
+                kinds[pos] = if let FitNode::Product(..) = self {
+                    RexKind::Product(tree)
+                } else {
+                    RexKind::Sum(tree)
+                };
+            }
+        }
+    }
+}
+
+/// Thin arrow rules are equal if their dot lists and polynomials are
+/// equal.  Warnings aren't compared.
+#[derive(Clone, Default, Debug)]
 pub struct ThinArrowRule {
-    dots:   DotList,
-    cause:  Polynomial,
-    effect: Polynomial,
+    dots:     DotList,
+    cause:    Polynomial,
+    effect:   Polynomial,
+    warnings: Vec<AscesisWarning>,
+    span:     NodeSpan,
 }
 
+impl PartialEq for ThinArrowRule {
+    fn eq(&self, other: &Self) -> bool {
+        self.dots == other.dots && self.cause == other.cause && self.effect == other.effect
+    }
+}
+
+impl Eq for ThinArrowRule {}
+
 impl ThinArrowRule {
     pub(crate) fn new() -> Self {
         Default::default()
     }
 
     pub(crate) fn with_dots(mut self, dots: Polynomial) -> Result<Self, AscesisError> {
+        self.warnings = dots.get_warnings().to_vec();
         self.dots = dots.try_into()?;
         Ok(self)
     }
 
+    #[inline]
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span.into();
+        self
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        *self.span
+    }
+
     pub(crate) fn with_cause(mut self, cause: Polynomial) -> Self {
         self.cause = cause;
         self
@@ -360,6 +801,86 @@ impl ThinArrowRule {
     pub fn get_dots(&self) -> &[DotName] {
         &self.dots.dot_names
     }
+
+    /// Returns warnings raised while parsing the dot list and both
+    /// polynomials of this rule.
+    pub(crate) fn get_warnings(&self) -> impl Iterator<Item = AscesisWarning> + '_ {
+        self.warnings
+            .iter()
+            .chain(self.cause.get_warnings())
+            .chain(self.effect.get_warnings())
+            .cloned()
+    }
+
+    /// Renames dots of this rule as [`Rex::rename_dots()`] does.
+    /// Dots which become equal after renaming are merged.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) {
+        self.dots.rename_dots(dot_map);
+        self.cause.rename_dots(dot_map);
+        self.effect.rename_dots(dot_map);
+    }
+
+    pub(crate) fn get_dot_names(&self) -> impl Iterator<Item = &DotName> {
+        self.dots
+            .dot_names
+            .iter()
+            .chain(self.cause.get_dot_names())
+            .chain(self.effect.get_dot_names())
+    }
+
+    /// Returns `true` if this rule has neither a cause nor an effect,
+    /// e.g. if it replaces an erased structure.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.cause.is_empty() && self.effect.is_empty()
+    }
+
+    /// Returns `true` if this rule specifies an effect of `tx_dot`
+    /// involving `rx_dot`, given [`Polarity::Tx`], or a cause of
+    /// `rx_dot` involving `tx_dot`, given [`Polarity::Rx`].
+    pub(crate) fn specifies_link(
+        &self,
+        tx_dot: &DotName,
+        rx_dot: &DotName,
+        polarity: Polarity,
+    ) -> bool {
+        match polarity {
+            Polarity::Tx => {
+                self.dots.dot_names.binary_search(tx_dot).is_ok()
+                    && self.effect.contains_dot(rx_dot)
+            }
+            Polarity::Rx => {
+                self.dots.dot_names.binary_search(rx_dot).is_ok() && self.cause.contains_dot(tx_dot)
+            }
+        }
+    }
+
+    /// Returns the key by which rules are ordered in the normal form:
+    /// effect-only rules come first, followed by two-polynomial rules
+    /// and cause-only rules, each ordered by their dot lists.
+    fn get_order_key(&self) -> (bool, bool, &[DotName]) {
+        (self.effect.is_empty(), !self.cause.is_empty(), &self.dots.dot_names)
+    }
+
+    pub(crate) fn contains_dot(&self, dot_name: &DotName) -> bool {
+        self.dots.dot_names.binary_search(dot_name).is_ok()
+            || self.cause.contains_dot(dot_name)
+            || self.effect.contains_dot(dot_name)
+    }
+}
+
+/// Prints a `ThinArrowRule` as an effect-only rule, a cause-only rule,
+/// or a forward rule, if it has both a cause and an effect.  An empty
+/// rule has no textual form of its own, and is printed as an empty
+/// pair of braces.
+impl fmt::Display for ThinArrowRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.cause.is_empty(), self.effect.is_empty()) {
+            (true, true) => write!(f, "{{}}"),
+            (true, false) => write!(f, "{} -> {}", self.dots, self.effect),
+            (false, true) => write!(f, "{} <- {}", self.dots, self.cause),
+            (false, false) => write!(f, "{} -> {} -> {}", self.cause, self.dots, self.effect),
+        }
+    }
 }
 
 impl CompilableAsContent for ThinArrowRule {
@@ -410,11 +931,13 @@ impl CompilableAsContent for ThinArrowRule {
 struct FatArrow {
     cause:  Polynomial,
     effect: Polynomial,
+    span:   NodeSpan,
 }
 
 #[derive(Clone, PartialEq, Eq, Default, Debug)]
 pub struct FatArrowRule {
     parts: Vec<FatArrow>,
+    span:  NodeSpan,
 }
 
 impl FatArrowRule {
@@ -425,16 +948,34 @@ impl FatArrowRule {
         let mut prev = head;
 
         for (op, poly) in tail.into_iter() {
+            let span = prev.span.join(*poly.span);
+
             match op {
                 BinOp::FatTx => {
-                    far.parts.push(FatArrow { cause: prev, effect: poly.clone() });
+                    far.parts.push(FatArrow {
+                        cause:  prev,
+                        effect: poly.clone(),
+                        span:   span.into(),
+                    });
                 }
                 BinOp::FatRx => {
-                    far.parts.push(FatArrow { cause: poly.clone(), effect: prev });
+                    far.parts.push(FatArrow {
+                        cause:  poly.clone(),
+                        effect: prev,
+                        span:   span.into(),
+                    });
                 }
                 BinOp::FatDx => {
-                    far.parts.push(FatArrow { cause: prev.clone(), effect: poly.clone() });
-                    far.parts.push(FatArrow { cause: poly.clone(), effect: prev });
+                    far.parts.push(FatArrow {
+                        cause:  prev.clone(),
+                        effect: poly.clone(),
+                        span:   span.into(),
+                    });
+                    far.parts.push(FatArrow {
+                        cause:  poly.clone(),
+                        effect: prev,
+                        span:   span.into(),
+                    });
                 }
                 _ => panic!("Operator not allowed in a fat arrow rule: '{}'.", op),
             }
@@ -442,6 +983,144 @@ impl FatArrowRule {
         }
         far
     }
+
+    #[inline]
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span.into();
+        self
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        *self.span
+    }
+
+    /// Renames dots of this rule as [`Rex::rename_dots()`] does.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) {
+        for part in self.parts.iter_mut() {
+            part.cause.rename_dots(dot_map);
+            part.effect.rename_dots(dot_map);
+        }
+    }
+
+    pub(crate) fn get_dot_names(&self) -> impl Iterator<Item = &DotName> {
+        self.parts
+            .iter()
+            .flat_map(|part| part.cause.get_dot_names().chain(part.effect.get_dot_names()))
+    }
+
+    pub(crate) fn contains_dot(&self, dot_name: &DotName) -> bool {
+        self.parts
+            .iter()
+            .any(|part| part.cause.contains_dot(dot_name) || part.effect.contains_dot(dot_name))
+    }
+
+    /// Returns warnings raised while parsing polynomials of this rule.
+    /// Each polynomial is visited once, although it may be shared by
+    /// two parts.
+    pub(crate) fn get_warnings(&self) -> Vec<AscesisWarning> {
+        if let Some((head, tail)) = self.get_chain() {
+            Some(head)
+                .into_iter()
+                .chain(tail.into_iter().map(|(_, poly)| poly))
+                .flat_map(|poly| poly.get_warnings())
+                .cloned()
+                .collect()
+        } else {
+            self.parts
+                .iter()
+                .flat_map(|part| part.cause.get_warnings().iter().chain(part.effect.get_warnings()))
+                .cloned()
+                .collect()
+        }
+    }
+
+    /// Rebuilds the sequence of polynomials and operators this rule
+    /// was created from by [`FatArrowRule::from_parts`].
+    fn get_chain(&self) -> Option<(&Polynomial, Vec<(BinOp, &Polynomial)>)> {
+        let first = self.parts.first()?;
+
+        for head in [&first.cause, &first.effect].iter() {
+            let mut tail = Vec::new();
+
+            if follow_chain(&self.parts, head, &mut tail) {
+                return Some((head, tail))
+            }
+        }
+
+        None
+    }
+}
+
+/// Extends `tail` with operators and polynomials following `prev` in
+/// a sequence which results in `parts`.  Returns `false` if there is
+/// no such sequence, leaving `tail` intact.
+fn follow_chain<'a>(
+    parts: &'a [FatArrow],
+    prev: &'a Polynomial,
+    tail: &mut Vec<(BinOp, &'a Polynomial)>,
+) -> bool {
+    let (part, rest) = match parts.split_first() {
+        Some(split) => split,
+        None => return true,
+    };
+
+    if &part.cause == prev {
+        if let Some((next, rest_dx)) = rest.split_first() {
+            if next.cause == part.effect && &next.effect == prev {
+                tail.push((BinOp::FatDx, &part.effect));
+
+                if follow_chain(rest_dx, &part.effect, tail) {
+                    return true
+                }
+                tail.pop();
+            }
+        }
+
+        tail.push((BinOp::FatTx, &part.effect));
+
+        if follow_chain(rest, &part.effect, tail) {
+            return true
+        }
+        tail.pop();
+    }
+
+    if &part.effect == prev {
+        tail.push((BinOp::FatRx, &part.cause));
+
+        if follow_chain(rest, &part.cause, tail) {
+            return true
+        }
+        tail.pop();
+    }
+
+    false
+}
+
+/// Prints a `FatArrowRule` as the sequence of polynomials and
+/// operators it was created from.
+impl fmt::Display for FatArrowRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((head, tail)) = self.get_chain() {
+            write!(f, "{}", head)?;
+
+            for (op, poly) in tail {
+                write!(f, " {} {}", op, poly)?;
+            }
+
+            Ok(())
+        } else {
+            // Not a sequence, hence not parsed, but put together by
+            // other means: print as a sum of two-polynomial rules.
+            let parts: Vec<_> = self
+                .parts
+                .iter()
+                .map(|part| format!("{{ {} => {} }}", part.cause, part.effect))
+                .collect();
+
+            write!(f, "{}", parts.join(" + "))
+        }
+    }
 }
 
 impl From<FatArrowRule> for Vec<ThinArrowRule> {
@@ -463,7 +1142,9 @@ impl From<&FatArrowRule> for Vec<ThinArrowRule> {
 
         // 1. Each two-polynomial (part of a) fat arrow rule is
         // replaced with a sum of two thin arrow rules, one
-        // effect-only, another cause-only.
+        // effect-only, another cause-only.  Both inherit the span of
+        // the part, and spans are joined whenever rules are merged in
+        // the steps below.
 
         let mut tx_tars = Vec::new();
         let mut rx_tars = Vec::new();
@@ -473,112 +1154,182 @@ impl From<&FatArrowRule> for Vec<ThinArrowRule> {
             let sinks = part.effect.flattened_clone();
 
             tx_tars.push(
-                ThinArrowRule::new().with_dots(sources).unwrap().with_effect(part.effect.clone()),
+                ThinArrowRule::new()
+                    .with_dots(sources)
+                    .unwrap()
+                    .with_effect(part.effect.clone())
+                    .with_span(*part.span),
             );
             rx_tars.push(
-                ThinArrowRule::new().with_dots(sinks).unwrap().with_cause(part.cause.clone()),
+                ThinArrowRule::new()
+                    .with_dots(sinks)
+                    .unwrap()
+                    .with_cause(part.cause.clone())
+                    .with_span(*part.span),
             );
         }
 
-        loop {
-            let mut at_fixpoint = true;
+        // Steps 2. to 4. are shared with folding of thin arrow rules
+        // of a sum.
 
-            // 2. The resulting rule expression is simplified by
-            // integrating effect-only rules having a common dot list
-            // and doing the same with cause-only rules.
+        fold_single_rules(tx_tars, rx_tars)
+    }
+}
 
-            let mut tx_tars_2: Vec<ThinArrowRule> = Vec::new();
-            let mut rx_tars_2: Vec<ThinArrowRule> = Vec::new();
+/// Folds a sum of thin arrow rules into the smallest equivalent sum,
+/// in canonical order.  Empty rules are dropped.
+fn fold_thin_rules<I>(tars: I) -> Vec<ThinArrowRule>
+where
+    I: IntoIterator<Item = ThinArrowRule>,
+{
+    // Two-polynomial rules are split into an effect-only rule and a
+    // cause-only rule, as in step 1. of FIT transformation.
+
+    let mut tx_tars = Vec::new();
+    let mut rx_tars = Vec::new();
+
+    for tar in tars {
+        if tar.is_empty() {
+            continue
+        }
 
-            'outer_tx_2: for mut tar_1 in tx_tars {
-                for tar_2 in tx_tars_2.iter_mut() {
-                    if tar_2.dots == tar_1.dots {
-                        tar_2.effect.add_assign(&mut tar_1.effect);
+        if !tar.effect.is_empty() {
+            let mut tx_tar = tar.clone();
 
-                        at_fixpoint = false;
-                        continue 'outer_tx_2
-                    }
+            tx_tar.cause = Polynomial::default();
+            tx_tars.push(tx_tar);
+        }
+
+        if !tar.cause.is_empty() {
+            let mut rx_tar = tar;
+
+            rx_tar.effect = Polynomial::default();
+            rx_tars.push(rx_tar);
+        }
+    }
+
+    fold_single_rules(tx_tars, rx_tars)
+}
+
+/// Folds a sum of effect-only rules, `tx_tars`, and cause-only rules,
+/// `rx_tars`, as in steps 2. to 4. of FIT transformation.
+///
+/// The result is sorted by [`ThinArrowRule::get_order_key`].  Warnings
+/// are dropped, since they refer to the textual form of rules, which
+/// isn't preserved.
+fn fold_single_rules(
+    mut tx_tars: Vec<ThinArrowRule>,
+    mut rx_tars: Vec<ThinArrowRule>,
+) -> Vec<ThinArrowRule> {
+    loop {
+        let mut at_fixpoint = true;
+
+        // 2. The resulting rule expression is simplified by
+        // integrating effect-only rules having a common dot list and
+        // doing the same with cause-only rules.
+
+        let mut tx_tars_2: Vec<ThinArrowRule> = Vec::new();
+        let mut rx_tars_2: Vec<ThinArrowRule> = Vec::new();
+
+        'outer_tx_2: for mut tar_1 in tx_tars {
+            for tar_2 in tx_tars_2.iter_mut() {
+                if tar_2.dots == tar_1.dots {
+                    tar_2.effect.add_assign(&mut tar_1.effect);
+                    tar_2.span = tar_2.span.join(*tar_1.span).into();
+
+                    at_fixpoint = false;
+                    continue 'outer_tx_2
                 }
-                tx_tars_2.push(tar_1);
             }
+            tx_tars_2.push(tar_1);
+        }
 
-            'outer_rx_2: for mut tar_1 in rx_tars {
-                for tar_2 in rx_tars_2.iter_mut() {
-                    if tar_2.dots == tar_1.dots {
-                        tar_2.cause.add_assign(&mut tar_1.cause);
+        'outer_rx_2: for mut tar_1 in rx_tars {
+            for tar_2 in rx_tars_2.iter_mut() {
+                if tar_2.dots == tar_1.dots {
+                    tar_2.cause.add_assign(&mut tar_1.cause);
+                    tar_2.span = tar_2.span.join(*tar_1.span).into();
 
-                        at_fixpoint = false;
-                        continue 'outer_rx_2
-                    }
+                    at_fixpoint = false;
+                    continue 'outer_rx_2
                 }
-                rx_tars_2.push(tar_1);
             }
+            rx_tars_2.push(tar_1);
+        }
 
-            // 3. Rule expression is further simplified by merging dot
-            // lists which point to the same effect polynomials, and
-            // merging dot lists pointed to by the same cause
-            // polynomials.
+        // 3. Rule expression is further simplified by merging dot
+        // lists which point to the same effect polynomials, and
+        // merging dot lists pointed to by the same cause polynomials.
 
-            let mut tx_tars_3: Vec<ThinArrowRule> = Vec::new();
-            let mut rx_tars_3: Vec<ThinArrowRule> = Vec::new();
+        let mut tx_tars_3: Vec<ThinArrowRule> = Vec::new();
+        let mut rx_tars_3: Vec<ThinArrowRule> = Vec::new();
 
-            'outer_tx_3: for mut tar_2 in tx_tars_2 {
-                for tar_3 in tx_tars_3.iter_mut() {
-                    if tar_3.effect == tar_2.effect {
-                        tar_3.dots.add_assign(&mut tar_2.dots);
+        'outer_tx_3: for mut tar_2 in tx_tars_2 {
+            for tar_3 in tx_tars_3.iter_mut() {
+                if tar_3.effect.monomials == tar_2.effect.monomials {
+                    tar_3.dots.add_assign(&mut tar_2.dots);
+                    tar_3.span = tar_3.span.join(*tar_2.span).into();
 
-                        at_fixpoint = false;
-                        continue 'outer_tx_3
-                    }
+                    at_fixpoint = false;
+                    continue 'outer_tx_3
                 }
-                tx_tars_3.push(tar_2);
             }
+            tx_tars_3.push(tar_2);
+        }
 
-            'outer_rx_3: for mut tar_2 in rx_tars_2 {
-                for tar_3 in rx_tars_3.iter_mut() {
-                    if tar_3.cause == tar_2.cause {
-                        tar_3.dots.add_assign(&mut tar_2.dots);
+        'outer_rx_3: for mut tar_2 in rx_tars_2 {
+            for tar_3 in rx_tars_3.iter_mut() {
+                if tar_3.cause.monomials == tar_2.cause.monomials {
+                    tar_3.dots.add_assign(&mut tar_2.dots);
+                    tar_3.span = tar_3.span.join(*tar_2.span).into();
 
-                        at_fixpoint = false;
-                        continue 'outer_rx_3
-                    }
+                    at_fixpoint = false;
+                    continue 'outer_rx_3
                 }
-                rx_tars_3.push(tar_2);
             }
+            rx_tars_3.push(tar_2);
+        }
 
-            // The result is a sum of single-polynomial thin arrow rules.
+        // The result is a sum of single-polynomial thin arrow rules.
 
-            tx_tars = tx_tars_3;
-            rx_tars = rx_tars_3;
+        tx_tars = tx_tars_3;
+        rx_tars = rx_tars_3;
 
-            // Steps 2. and 3. are repeated, until a fixed point is
-            // reached.
+        // Steps 2. and 3. are repeated, until a fixed point is
+        // reached.
 
-            if at_fixpoint {
-                break
-            }
+        if at_fixpoint {
+            break
         }
+    }
 
-        // 4. Any pair of rules with the same dot list is combined
-        // into a two-polynomial rule.
+    // 4. Any pair of rules with the same dot list is combined into a
+    // two-polynomial rule.
 
-        'outer_4: for rx_tar in rx_tars {
-            for tx_tar in tx_tars.iter_mut() {
-                if rx_tar.dots == tx_tar.dots {
-                    tx_tar.cause = rx_tar.cause;
-                    continue 'outer_4
-                }
+    'outer_4: for rx_tar in rx_tars {
+        for tx_tar in tx_tars.iter_mut() {
+            if rx_tar.dots == tx_tar.dots {
+                tx_tar.cause = rx_tar.cause;
+                tx_tar.span = tx_tar.span.join(*rx_tar.span).into();
+                continue 'outer_4
             }
-            tx_tars.push(rx_tar);
         }
+        tx_tars.push(rx_tar);
+    }
 
-        tx_tars
+    for tar in tx_tars.iter_mut() {
+        tar.warnings.clear();
+        tar.cause.normalize();
+        tar.effect.normalize();
     }
+
+    tx_tars.sort_by(|tar_1, tar_2| tar_1.get_order_key().cmp(&tar_2.get_order_key()));
+
+    tx_tars
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ToCesName;
     use super::*;
 
     #[test]
@@ -591,49 +1342,70 @@ mod tests {
             rex,
             Rex {
                 kinds: vec![
-                    RexKind::Sum(RexTree { ids: vec![1, 8] }),
-                    RexKind::Product(RexTree { ids: vec![2, 3] }),
+                    RexKind::Sum(RexTree { ids: vec![1, 8], ..Default::default() }),
+                    RexKind::Product(RexTree { ids: vec![2, 3], ..Default::default() }),
                     RexKind::Fat(FatArrowRule {
                         parts: vec![
                             FatArrow {
                                 cause:  Polynomial::from("a"),
                                 effect: Polynomial::from("b"),
+                                span:   Default::default(),
                             },
                             FatArrow {
                                 cause:  Polynomial::from("c"),
                                 effect: Polynomial::from("b"),
+                                span:   Default::default(),
                             }
                         ],
+                        span:  Default::default(),
+                    }),
+                    RexKind::Sum(RexTree { ids: vec![4, 5], ..Default::default() }),
+                    RexKind::Instance(CesInstance {
+                        name: "d".to_ces_name(),
+                        args: vec![],
+                        span: Default::default(),
                     }),
-                    RexKind::Sum(RexTree { ids: vec![4, 5] }),
-                    RexKind::Instance(CesInstance { name: "d".to_ces_name(), args: vec![] }),
-                    RexKind::Product(RexTree { ids: vec![6, 7] }),
+                    RexKind::Product(RexTree { ids: vec![6, 7], ..Default::default() }),
                     RexKind::Instance(CesInstance {
                         name: "e".to_ces_name(),
-                        args: vec!["f".to_string()],
+                        args: vec![ArgValue::Identifier("f".to_string())],
+                        span: Default::default(),
                     }),
                     RexKind::Instance(CesInstance {
                         name: "g".to_ces_name(),
-                        args: vec!["h".to_string(), "i".to_string()],
+                        args: vec![
+                            ArgValue::Identifier("h".to_string()),
+                            ArgValue::Identifier("i".to_string()),
+                        ],
+                        span: Default::default(),
                     }),
-                    RexKind::Product(RexTree { ids: vec![9, 13] }),
-                    RexKind::Product(RexTree { ids: vec![10, 11, 12] }),
+                    RexKind::Product(RexTree { ids: vec![9, 13], ..Default::default() }),
+                    RexKind::Product(RexTree { ids: vec![10, 11, 12], ..Default::default() }),
                     RexKind::Thin(ThinArrowRule {
-                        dots:   DotList::from(vec!["k"]),
-                        cause:  Polynomial::from("j"),
-                        effect: Polynomial::from("l"),
+                        dots:     DotList::from(vec!["k"]),
+                        cause:    Polynomial::from("j"),
+                        effect:   Polynomial::from("l"),
+                        warnings: Vec::new(),
+                        span:     Default::default(),
                     }),
                     RexKind::Thin(ThinArrowRule {
-                        dots:   DotList::from(vec!["j"]),
-                        cause:  Polynomial::default(),
-                        effect: Polynomial::from("k"),
+                        dots:     DotList::from(vec!["j"]),
+                        cause:    Polynomial::default(),
+                        effect:   Polynomial::from("k"),
+                        warnings: Vec::new(),
+                        span:     Default::default(),
                     }),
                     RexKind::Thin(ThinArrowRule {
-                        dots:   DotList::from(vec!["l"]),
-                        cause:  Polynomial::from("k"),
-                        effect: Polynomial::default(),
+                        dots:     DotList::from(vec!["l"]),
+                        cause:    Polynomial::from("k"),
+                        effect:   Polynomial::default(),
+                        warnings: Vec::new(),
+                        span:     Default::default(),
+                    }),
+                    RexKind::Immediate(CesImmediate {
+                        name: "m".to_ces_name(),
+                        span: Default::default(),
                     }),
-                    RexKind::Immediate(CesImmediate { name: "m".to_ces_name() }),
                 ],
             }
         );
@@ -651,7 +1423,9 @@ mod tests {
                     parts: vec![FatArrow {
                         cause:  Polynomial::from("a"),
                         effect: Polynomial::from("b"),
+                        span:   Default::default(),
                     },],
+                    span:  Default::default(),
                 }),],
             }
         );
@@ -662,16 +1436,20 @@ mod tests {
             rex,
             Rex {
                 kinds: vec![
-                    RexKind::Sum(RexTree { ids: vec![1, 2] }),
+                    RexKind::Sum(RexTree { ids: vec![1, 2], ..Default::default() }),
                     RexKind::Thin(ThinArrowRule {
-                        dots:   DotList::from(vec!["a"]),
-                        cause:  Polynomial::default(),
-                        effect: Polynomial::from("b"),
+                        dots:     DotList::from(vec!["a"]),
+                        cause:    Polynomial::default(),
+                        effect:   Polynomial::from("b"),
+                        warnings: Vec::new(),
+                        span:     Default::default(),
                     }),
                     RexKind::Thin(ThinArrowRule {
-                        dots:   DotList::from(vec!["b"]),
-                        cause:  Polynomial::from("a"),
-                        effect: Polynomial::default(),
+                        dots:     DotList::from(vec!["b"]),
+                        cause:    Polynomial::from("a"),
+                        effect:   Polynomial::default(),
+                        warnings: Vec::new(),
+                        span:     Default::default(),
                     }),
                 ],
             }
@@ -688,21 +1466,27 @@ mod tests {
             rex,
             Rex {
                 kinds: vec![
-                    RexKind::Sum(RexTree { ids: vec![1, 2, 3] }),
+                    RexKind::Sum(RexTree { ids: vec![1, 2, 3], ..Default::default() }),
                     RexKind::Thin(ThinArrowRule {
-                        dots:   DotList::from(vec!["a"]),
-                        cause:  Polynomial::default(),
-                        effect: Polynomial::from("b"),
+                        dots:     DotList::from(vec!["a"]),
+                        cause:    Polynomial::default(),
+                        effect:   Polynomial::from("b"),
+                        warnings: Vec::new(),
+                        span:     Default::default(),
                     }),
                     RexKind::Thin(ThinArrowRule {
-                        dots:   DotList::from(vec!["b"]),
-                        cause:  Polynomial::from("a"),
-                        effect: Polynomial::from("c"),
+                        dots:     DotList::from(vec!["b"]),
+                        cause:    Polynomial::from("a"),
+                        effect:   Polynomial::from("c"),
+                        warnings: Vec::new(),
+                        span:     Default::default(),
                     }),
                     RexKind::Thin(ThinArrowRule {
-                        dots:   DotList::from(vec!["c"]),
-                        cause:  Polynomial::from("b"),
-                        effect: Polynomial::default(),
+                        dots:     DotList::from(vec!["c"]),
+                        cause:    Polynomial::from("b"),
+                        effect:   Polynomial::default(),
+                        warnings: Vec::new(),
+                        span:     Default::default(),
                     }),
                 ],
             }
@@ -719,19 +1503,104 @@ mod tests {
             rex,
             Rex {
                 kinds: vec![
-                    RexKind::Sum(RexTree { ids: vec![1, 2] }),
+                    RexKind::Sum(RexTree { ids: vec![1, 2], ..Default::default() }),
                     RexKind::Thin(ThinArrowRule {
-                        dots:   DotList::from(vec!["b"]),
-                        cause:  Polynomial::default(),
-                        effect: Polynomial::from(vec![vec!["a"], vec!["c"]]),
+                        dots:     DotList::from(vec!["b"]),
+                        cause:    Polynomial::default(),
+                        effect:   Polynomial::from(vec![vec!["a"], vec!["c"]]),
+                        warnings: Vec::new(),
+                        span:     Default::default(),
                     }),
                     RexKind::Thin(ThinArrowRule {
-                        dots:   DotList::from(vec!["a", "c"]),
-                        cause:  Polynomial::from("b"),
-                        effect: Polynomial::default(),
+                        dots:     DotList::from(vec!["a", "c"]),
+                        cause:    Polynomial::from("b"),
+                        effect:   Polynomial::default(),
+                        warnings: Vec::new(),
+                        span:     Default::default(),
                     }),
                 ],
             }
         );
     }
+
+    #[test]
+    fn test_rex_dependencies() {
+        let phrase = "A() + { a => b } Arrow!(a, b)";
+        let rex: Rex = phrase.parse().unwrap();
+        let deps = rex.get_dependencies();
+
+        assert_eq!(deps.len(), 2);
+        assert_eq!(deps[0].0, "A".to_ces_name());
+        assert_eq!((deps[0].1.start(), deps[0].1.end()), (0, 3));
+        assert_eq!(deps[1].0, "Arrow!(a, b)".to_ces_name());
+        assert_eq!((deps[1].1.start(), deps[1].1.end()), (17, 29));
+    }
+
+    #[test]
+    fn test_fit_spans() {
+        let phrase = "{ a => b <= c } + { d -> e }";
+        let rex: Rex = phrase.parse().unwrap();
+        let spans: Vec<_> =
+            rex.kinds.iter().map(|k| (k.get_span().start(), k.get_span().end())).collect();
+
+        assert_eq!(spans, vec![(0, 28), (2, 13), (20, 26)]);
+
+        let rex = rex.fit_clone();
+        let spans: Vec<_> =
+            rex.kinds.iter().map(|k| (k.get_span().start(), k.get_span().end())).collect();
+
+        assert_eq!(spans, vec![(0, 28), (2, 13), (20, 26), (2, 13)]);
+    }
+
+    #[test]
+    fn test_fit_normal_form() {
+        let phrase = "{ a => b <= c } + { d -> e } + A()";
+        let rex: Rex = phrase.parse().unwrap();
+        let rex = rex.fit_clone();
+
+        assert_eq!(rex.to_string(), "{ a c -> b } + { d -> e } + { b <- a + c } + A()");
+
+        let phrase =
+            "A() + { { b <- c } + { { b <- a } + { c -> b } } } + { { a -> b } + { d -> e } }";
+        let other: Rex = phrase.parse().unwrap();
+
+        assert_eq!(other.fit_clone(), rex);
+
+        let phrase = "{ { B() A() } { a -> b } } { { a -> c } + { a -> b } }";
+        let rex: Rex = phrase.parse().unwrap();
+
+        assert_eq!(rex.fit_clone().to_string(), "{ a -> b } { a -> b + c } A() B()");
+    }
+
+    #[test]
+    fn test_rex_display() {
+        let phrase = "{ a => b <= c } { d!() + e!(f) g!(h, i) } + { { j -> k -> l } { j -> k } { \
+                      l <- k } } m()";
+        let rex: Rex = phrase.parse().unwrap();
+        let printed = rex.to_string();
+
+        assert_eq!(printed, phrase);
+        assert_eq!(printed.parse::<Rex>().unwrap(), rex);
+
+        let phrase = "a => b c <=> d + e <= f";
+        let rex: Rex = phrase.parse().unwrap();
+
+        assert_eq!(rex.to_string(), "a => b c <=> d + e <= f");
+    }
+
+    #[test]
+    fn test_rex_round_trip() {
+        for (phrase, expected) in &[
+            ("a a -> b + b", "a -> b"),
+            ("{ a a -> b c + c }", "a -> b c + c"),
+            ("a b + b a => c <= (d d)", "a b => c <= d"),
+        ] {
+            let rex: Rex = phrase.parse().unwrap();
+            let printed = rex.to_string();
+
+            assert!(!rex.get_warnings().is_empty());
+            assert_eq!(&printed, expected);
+            assert_eq!(printed.parse::<Rex>().unwrap(), rex);
+        }
+    }
 }
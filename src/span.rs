@@ -0,0 +1,127 @@
+use std::{
+    fmt,
+    ops::{Range, Deref},
+};
+
+/// Byte range of a syntactic construct in the script it was parsed
+/// from.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Span {
+    start: usize,
+    end:   usize,
+}
+
+impl Span {
+    #[inline]
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    #[inline]
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    #[inline]
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Returns `true` for an empty span, e.g. a span of a syntactic
+    /// construct which wasn't parsed, but created programmatically.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+
+    /// Returns the smallest span covering both `self` and `other`.
+    /// An empty span is absorbed by the other one.
+    pub fn join(self, other: Self) -> Self {
+        if self.is_empty() {
+            other
+        } else if other.is_empty() {
+            self
+        } else {
+            Span { start: self.start.min(other.start), end: self.end.max(other.end) }
+        }
+    }
+}
+
+/// Returns the one-based line and column numbers of a byte offset in
+/// `script`, where columns are counted in characters.
+///
+/// An offset past the end of `script` is located right after its last
+/// character, and an offset inside a multi-byte character is located
+/// at that character.
+pub(crate) fn line_column(script: &str, pos: usize) -> (usize, usize) {
+    let mut pos = pos.min(script.len());
+
+    while !script.is_char_boundary(pos) {
+        pos -= 1;
+    }
+
+    let before = &script[..pos];
+    let line_start = before.rfind('\n').map_or(0, |ndx| ndx + 1);
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+
+    (line, column)
+}
+
+impl From<Span> for Range<usize> {
+    #[inline]
+    fn from(span: Span) -> Self {
+        span.start..span.end
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// Span of a syntax tree node.
+///
+/// Spans carry no meaning of their own, hence any two node spans
+/// compare equal, so that syntax trees parsed from different places
+/// are equal whenever their contents are.
+#[derive(Clone, Copy, Default, Debug)]
+pub(crate) struct NodeSpan(Span);
+
+impl PartialEq for NodeSpan {
+    #[inline]
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for NodeSpan {}
+
+impl Deref for NodeSpan {
+    type Target = Span;
+
+    #[inline]
+    fn deref(&self) -> &Span {
+        &self.0
+    }
+}
+
+impl From<Span> for NodeSpan {
+    #[inline]
+    fn from(span: Span) -> Self {
+        NodeSpan(span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_eq() {
+        assert_eq!(Span::new(2, 5), Span::new(2, 5));
+        assert_ne!(Span::new(2, 5), Span::new(3, 5));
+        assert_eq!(NodeSpan::from(Span::new(2, 5)), NodeSpan::from(Span::new(3, 5)));
+    }
+}
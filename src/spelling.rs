@@ -0,0 +1,81 @@
+//! Similarity of names, for suggesting corrections of misspelled
+//! ones.
+
+/// Returns the edit distance between `a` and `b`, i.e. the smallest
+/// number of characters inserted, deleted or substituted, and of
+/// adjacent characters transposed, which turns one into the other.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance =
+                (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+
+            rows[i][j] = distance;
+        }
+    }
+
+    rows[a.len()][b.len()]
+}
+
+/// Returns the candidate most similar to `name`, unless none is
+/// similar enough to be a likely correction.
+///
+/// A candidate is similar enough, if it differs from `name` by at
+/// most one character in three, but at least one.  Ties are resolved
+/// in favor of the candidate coming first.
+pub(crate) fn find_similar<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut best = None;
+
+    for candidate in candidates {
+        if candidate == name {
+            continue
+        }
+
+        let distance = edit_distance(name, candidate);
+
+        if distance <= max_distance && !matches!(best, Some((_, d)) if d <= distance) {
+            best = Some((candidate, distance));
+        }
+    }
+
+    best.map(|(candidate, _)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similar() {
+        assert_eq!(edit_distance("Arow", "Arrow"), 1);
+        assert_eq!(edit_distance("lables", "labels"), 1);
+        assert_eq!(edit_distance("Mian", "Main"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+
+        assert_eq!(find_similar("Arow", vec!["Main", "Arrow", "Arrows"]), Some("Arrow"));
+        assert_eq!(find_similar("lables", vec!["title", "labels"]), Some("labels"));
+        assert_eq!(find_similar("a", vec!["a", "xyz"]), None);
+        assert_eq!(find_similar("Main", vec!["Source", "Sink"]), None);
+    }
+}
@@ -0,0 +1,549 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    str::FromStr,
+};
+use crate::{
+    CesFileBlock, CesName, ImmediateDef, CesInstance, Rex, Literal, DotName, ToDotName, ToCesName,
+    Span, AscesisError, AscesisErrorKind, AscesisWarning,
+    ces::{join_doc_lines, fmt_doc_lines},
+};
+
+/// Type of a template argument, as declared in a template signature.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ArgType {
+    Dot,
+    CES,
+    Size,
+    Name,
+    Hybrid,
+}
+
+impl fmt::Display for ArgType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ArgType::*;
+
+        match self {
+            Dot => write!(f, "Dot"),
+            CES => write!(f, "CES"),
+            Size => write!(f, "Size"),
+            Name => write!(f, "Name"),
+            Hybrid => write!(f, "Hybrid"),
+        }
+    }
+}
+
+/// Parses a type name given in an argument declaration.  Type names
+/// are recognized only in that position, not by the lexer.
+impl FromStr for ArgType {
+    type Err = AscesisError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Dot" => Ok(ArgType::Dot),
+            "CES" => Ok(ArgType::CES),
+            "Size" => Ok(ArgType::Size),
+            "Name" => Ok(ArgType::Name),
+            "Hybrid" => Ok(ArgType::Hybrid),
+            _ => Err(AscesisErrorKind::ArgTypeUnknown(s.to_owned()).into()),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ArgDecl {
+    name:     String,
+    arg_type: ArgType,
+}
+
+impl ArgDecl {
+    pub(crate) fn new(name: String, arg_type: ArgType) -> Self {
+        ArgDecl { name, arg_type }
+    }
+
+    #[inline]
+    pub fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    #[inline]
+    pub fn get_type(&self) -> ArgType {
+        self.arg_type
+    }
+}
+
+impl fmt::Display for ArgDecl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.arg_type)
+    }
+}
+
+/// Argument passed to a template instance: a dot name (identifier),
+/// a size literal, or a name literal.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ArgValue {
+    Identifier(String),
+    Literal(Literal),
+}
+
+impl fmt::Display for ArgValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgValue::Identifier(identifier) => identifier.fmt(f),
+            ArgValue::Literal(lit) => lit.fmt(f),
+        }
+    }
+}
+
+/// Structure definition, template form.
+///
+/// An empty argument list is valid and defines a _black hole_.
+///
+/// The body of a template may end with context blocks local to the
+/// template.  These are instantiated along with the rule expression,
+/// so that `Size` and `Name` arguments may be used inside them.
+#[derive(Clone, Debug)]
+pub struct TemplateDef {
+    pub(crate) name: CesName,
+    args:            Vec<ArgDecl>,
+    pub(crate) rex:  Rex,
+    blocks:          Vec<CesFileBlock>,
+    doc:             Option<String>,
+    span:            Span,
+}
+
+impl TemplateDef {
+    pub fn new(name: CesName, args: Vec<ArgDecl>, rex: Rex) -> Result<Self, AscesisError> {
+        let mut arg_names = BTreeSet::new();
+
+        for arg in args.iter() {
+            if !arg_names.insert(arg.name.as_str()) {
+                return Err(AscesisErrorKind::TemplateArgRedefined(arg.name.clone()).into())
+            }
+        }
+
+        debug!("TemplateDef of '{}' {:?}: {:?}", name, args, rex);
+        Ok(TemplateDef { name, args, rex, blocks: Vec::new(), doc: None, span: Span::default() })
+    }
+
+    /// Appends local context blocks, failing if a capacity is given
+    /// by a name other than that of a `Size` argument.
+    pub(crate) fn with_blocks(
+        mut self,
+        mut blocks: Vec<CesFileBlock>,
+    ) -> Result<Self, AscesisError> {
+        for block in blocks.iter() {
+            if let CesFileBlock::Caps(caps) = block {
+                caps.check_size_params(|param| {
+                    self.args.iter().any(|arg| arg.name == param && arg.arg_type == ArgType::Size)
+                })?;
+            }
+        }
+
+        self.blocks.append(&mut blocks);
+        Ok(self)
+    }
+
+    /// Attaches documentation given as a sequence of doc comment
+    /// lines, which are joined with newlines.
+    pub fn with_doc(mut self, lines: Vec<String>) -> Self {
+        self.doc = join_doc_lines(lines);
+        self
+    }
+
+    #[inline]
+    pub(crate) fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+
+    #[inline]
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+
+    #[inline]
+    pub fn get_name(&self) -> &CesName {
+        &self.name
+    }
+
+    #[inline]
+    pub fn get_args(&self) -> &[ArgDecl] {
+        self.args.as_slice()
+    }
+
+    pub(crate) fn get_arg_types(&self) -> Vec<ArgType> {
+        self.args.iter().map(ArgDecl::get_type).collect()
+    }
+
+    #[inline]
+    pub fn get_rex(&self) -> &Rex {
+        &self.rex
+    }
+
+    #[inline]
+    pub fn get_blocks(&self) -> &[CesFileBlock] {
+        self.blocks.as_slice()
+    }
+
+    #[inline]
+    pub fn get_doc(&self) -> Option<&str> {
+        self.doc.as_deref()
+    }
+
+    /// Renames dots in the rule expression and in local context
+    /// blocks of this template.  Arguments aren't renamed: a dot
+    /// named after an argument is left as it is, since it stands for
+    /// whatever the argument is bound to.
+    ///
+    /// Returns a warning for each capacity or label of a local block
+    /// dropped after renaming.
+    pub fn rename_dots(&mut self, dot_map: &BTreeMap<DotName, DotName>) -> Vec<AscesisWarning> {
+        let dot_map: BTreeMap<_, _> = dot_map
+            .iter()
+            .filter(|(dot_name, _)| self.args.iter().all(|arg| arg.name != dot_name.as_ref()))
+            .map(|(dot_name, new_name)| (dot_name.clone(), new_name.clone()))
+            .collect();
+
+        self.rex.rename_dots(&dot_map);
+
+        self.blocks.iter_mut().flat_map(|block| block.rename_dots(&dot_map)).collect()
+    }
+
+    /// Returns `true` if a dot named `dot_name` occurs in the rule
+    /// expression or in a local context block of this template.
+    fn uses_dot(&self, dot_name: &str) -> bool {
+        self.rex.contains_dot(&dot_name.to_dot())
+            || self.blocks.iter().any(|block| block.get_dot_names().contains(dot_name))
+    }
+
+    /// Binds arguments of the `instance` to this template's arguments
+    /// and returns the resulting structure definition, together with
+    /// the instantiated local context blocks.
+    ///
+    /// The definition is named after the `instance` and its rule
+    /// expression is a copy of this template's rule expression, where
+    /// each `Dot` argument is replaced with the dot passed in, and each
+    /// immediate instantiation of a `CES` argument is replaced with an
+    /// immediate instantiation of the structure passed in.  In local
+    /// context blocks, dots are replaced likewise, and each use
+    /// of a `Size` or `Name` argument is replaced with the literal
+    /// passed in.
+    ///
+    /// A `Hybrid` argument is bound to a structure if `is_structure`
+    /// returns `true` for the name passed in, and then it is treated
+    /// as a `CES` argument, which must not be used as a dot.
+    /// Otherwise, a `Hybrid` argument is bound to a dot, and each of
+    /// its immediate instantiations is replaced with an empty thin
+    /// arrow rule.
+    pub fn instantiate<F>(
+        &self,
+        instance: &CesInstance,
+        is_structure: F,
+    ) -> Result<(ImmediateDef, Vec<CesFileBlock>), AscesisError>
+    where
+        F: Fn(&CesName) -> bool,
+    {
+        if instance.args.len() != self.args.len() {
+            return Err(AscesisErrorKind::TemplateArityMismatch(
+                self.name.to_string(),
+                self.args.len(),
+                instance.args.len(),
+            )
+            .into())
+        }
+
+        let mut dot_map = BTreeMap::new();
+        let mut ces_map = BTreeMap::new();
+        let mut erased = BTreeSet::new();
+        let mut literals = BTreeMap::new();
+        let mut args = BTreeMap::new();
+
+        for (decl, value) in self.args.iter().zip(instance.args.iter()) {
+            match (decl.arg_type, value) {
+                (ArgType::Dot, ArgValue::Identifier(dot_name)) => {
+                    dot_map.insert(decl.name.to_dot(), dot_name.to_dot());
+                }
+                (ArgType::CES, ArgValue::Identifier(ces_name)) => {
+                    ces_map.insert(decl.name.to_ces_name(), ces_name.to_ces_name());
+                }
+                (ArgType::Hybrid, ArgValue::Identifier(name)) => {
+                    if is_structure(&name.to_ces_name()) {
+                        if self.uses_dot(&decl.name) {
+                            return Err(AscesisErrorKind::HybridArgMisuse(
+                                self.name.to_string(),
+                                decl.name.clone(),
+                            )
+                            .into())
+                        }
+
+                        ces_map.insert(decl.name.to_ces_name(), name.to_ces_name());
+                    } else {
+                        dot_map.insert(decl.name.to_dot(), name.to_dot());
+                        erased.insert(decl.name.to_ces_name());
+                    }
+                }
+                (ArgType::Size, ArgValue::Literal(lit @ Literal::Size(_)))
+                | (ArgType::Size, ArgValue::Literal(lit @ Literal::Omega))
+                | (ArgType::Name, ArgValue::Literal(lit @ Literal::Name(_))) => {
+                    literals.insert(decl.name.clone(), lit.clone());
+                }
+                (arg_type, _) => {
+                    return Err(AscesisErrorKind::TemplateArgTypeMismatch(
+                        self.name.to_string(),
+                        decl.name.clone(),
+                        arg_type,
+                    )
+                    .into())
+                }
+            }
+
+            args.insert(decl.name.clone(), value.clone());
+        }
+
+        // Arguments of nested instantiations are substituted in a
+        // single pass, apart from dots of rules and names of immediate
+        // instantiations, so that no value is substituted twice.
+        let mut rex = self.rex.clone();
+        rex.erase_structures(&erased);
+        rex.rename_rule_dots(&dot_map);
+        rex.rename_structures(&ces_map);
+        rex.substitute_args(&args);
+
+        let mut blocks = self.blocks.clone();
+
+        for block in blocks.iter_mut() {
+            block.rename_dots(&dot_map);
+            block.bind_literals(&literals)?;
+        }
+
+        Ok((ImmediateDef::new(instance.get_instance_name(), rex), blocks))
+    }
+}
+
+/// Prints a `TemplateDef` as _Ascesis_ source, preceded by its doc
+/// comments, if any.  A template with local context blocks is printed
+/// on multiple lines, one line for the rule expression and one for
+/// each block.
+impl fmt::Display for TemplateDef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_doc_lines(self.get_doc(), f)?;
+
+        let args: Vec<_> = self.args.iter().map(|arg| arg.to_string()).collect();
+        let rex = self.rex.to_string();
+
+        write!(f, "ces {}({}) {{", self.name, args.join(", "))?;
+
+        if self.blocks.is_empty() {
+            if rex.is_empty() {
+                write!(f, "}}")
+            } else {
+                write!(f, " {} }}", rex)
+            }
+        } else {
+            if !rex.is_empty() {
+                write!(f, "\n    {}", rex)?;
+            }
+
+            for block in self.blocks.iter() {
+                write!(f, "\n    {}", block)?;
+            }
+
+            write!(f, "\n}}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CesFile, CapacitiesBlock, PropBlock};
+    use super::*;
+
+    #[test]
+    fn test_template_sig() {
+        let phrase = "ces Arrow(x: Dot, y: Dot,) { x => y }";
+        let template: TemplateDef = phrase.parse().unwrap();
+
+        assert_eq!(template.get_name(), &"Arrow".to_ces_name());
+        assert_eq!(
+            template.get_args(),
+            &[ArgDecl::new("x".into(), ArgType::Dot), ArgDecl::new("y".into(), ArgType::Dot)]
+        );
+        assert_eq!(template.rex, "x => y".parse().unwrap());
+    }
+
+    #[test]
+    fn test_template_black_hole() {
+        let phrase = "ces Hole() {}";
+        let template: TemplateDef = phrase.parse().unwrap();
+
+        assert!(template.get_args().is_empty());
+        assert_eq!(template.rex, Rex::new());
+    }
+
+    #[test]
+    fn test_template_arg_types() {
+        let phrase = "ces Size(Dot: Dot, Name: Size) { Dot => CES Hybrid }";
+        let template: TemplateDef = phrase.parse().unwrap();
+
+        assert_eq!(template.get_args()[1], ArgDecl::new("Name".into(), ArgType::Size));
+        assert!(template.rex.contains_dot(&"CES".to_dot()));
+
+        let err = "ces Arrow(x: Dots) { x => y }".parse::<TemplateDef>().unwrap_err();
+
+        assert_eq!(err.to_string(), "Unknown argument type 'Dots'");
+    }
+
+    #[test]
+    fn test_template_arg_redefined() {
+        let phrase = "ces Loop(x: Dot, x: CES) { x => x }";
+
+        assert!(phrase.parse::<TemplateDef>().is_err());
+    }
+
+    #[test]
+    fn test_template_instance() {
+        let template: TemplateDef =
+            "ces Fork(x: Dot, y: Dot, z: Dot) { x => y z }".parse().unwrap();
+        let instance: CesInstance = "Fork!(a, b, a)".parse().unwrap();
+        let (imm, _) = template.instantiate(&instance, |_| false).unwrap();
+
+        assert_eq!(imm.name, "Fork!(a, b, a)".to_ces_name());
+        assert_eq!(imm.rex, "a => a b".parse().unwrap());
+    }
+
+    #[test]
+    fn test_template_nested_instance() {
+        let template: TemplateDef =
+            "ces Chain(x: Dot, y: Dot) { Arrow!(x, y) + Arrow!(y, z) }".parse().unwrap();
+        let instance: CesInstance = "Chain!(a, b)".parse().unwrap();
+        let (imm, _) = template.instantiate(&instance, |_| false).unwrap();
+        let instances: Vec<_> = imm.rex.get_instances().map(|i| i.to_string()).collect();
+
+        assert_eq!(instances, vec!["Arrow!(a, b)", "Arrow!(b, z)"]);
+    }
+
+    #[test]
+    fn test_template_ces_args() {
+        let template: TemplateDef =
+            "ces Pipe3(f: CES, g: CES, h: CES) { Pipe!(f, g) h() }".parse().unwrap();
+        let instance: CesInstance = "Pipe3!(A, B, C)".parse().unwrap();
+        let (imm, _) = template.instantiate(&instance, |_| false).unwrap();
+
+        assert_eq!(imm.rex, "Pipe!(A, B) C()".parse().unwrap());
+    }
+
+    #[test]
+    fn test_template_args_substituted_once() {
+        let template: TemplateDef =
+            "ces T(x: Dot, f: CES, n: Size) { U!(x, f, n) + f() }".parse().unwrap();
+        let instance: CesInstance = "T!(f, A, 3)".parse().unwrap();
+        let (imm, _) = template.instantiate(&instance, |_| false).unwrap();
+        let instances: Vec<_> = imm.rex.get_instances().map(|i| i.to_string()).collect();
+
+        assert_eq!(instances, vec!["U!(f, A, 3)"]);
+        assert!(imm.rex.get_dependencies().iter().any(|(name, _)| name.as_str() == "A"));
+
+        let template: TemplateDef = "ces Swap(x: Dot, y: Dot) { Arrow!(y, x) }".parse().unwrap();
+        let instance: CesInstance = "Swap!(y, x)".parse().unwrap();
+        let (imm, _) = template.instantiate(&instance, |_| false).unwrap();
+        let instances: Vec<_> = imm.rex.get_instances().map(|i| i.to_string()).collect();
+
+        assert_eq!(instances, vec!["Arrow!(x, y)"]);
+    }
+
+    #[test]
+    fn test_template_hybrid_args() {
+        let template: TemplateDef = "ces Grow(x: Hybrid) { x() { x => y } }".parse().unwrap();
+        let instance: CesInstance = "Grow!(a)".parse().unwrap();
+        let (imm, _) = template.instantiate(&instance, |_| false).unwrap();
+
+        assert_eq!(imm.rex.get_instances().count(), 0);
+        assert!(imm.rex.contains_dot(&"a".to_dot()));
+
+        let template: TemplateDef = "ces Grow(x: Hybrid) { x() { y => z } }".parse().unwrap();
+        let instance: CesInstance = "Grow!(A)".parse().unwrap();
+        let (imm, _) = template.instantiate(&instance, |name| name.as_str() == "A").unwrap();
+
+        assert_eq!(imm.rex, "A() { y => z }".parse().unwrap());
+
+        let template: TemplateDef = "ces Grow(x: Hybrid) { x() { x => y } }".parse().unwrap();
+
+        assert!(template.instantiate(&instance, |name| name.as_str() == "A").is_err());
+
+        let template: TemplateDef =
+            "ces Grow(x: Hybrid) { x() { y => z } caps { 2 x } }".parse().unwrap();
+
+        assert!(template.instantiate(&instance, |name| name.as_str() == "A").is_err());
+    }
+
+    #[test]
+    fn test_template_arity_mismatch() {
+        let template: TemplateDef = "ces Arrow(x: Dot, y: Dot) { x => y }".parse().unwrap();
+        let instance: CesInstance = "Arrow!(a)".parse().unwrap();
+
+        assert!(template.instantiate(&instance, |_| false).is_err());
+    }
+
+    #[test]
+    fn test_template_typed_args() {
+        let template: TemplateDef = r#"
+ces Buffer(x: Dot, cap: Size, label: Name) {
+    x => y
+    caps { cap x }
+    vis { labels: { x: label } }
+}"#
+        .parse()
+        .unwrap();
+        let instance: CesInstance = r#"Buffer!(a, 3, "Input")"#.parse().unwrap();
+        let (imm, blocks) = template.instantiate(&instance, |_| false).unwrap();
+
+        assert_eq!(imm.name, r#"Buffer!(a, 3, "Input")"#.to_ces_name());
+        assert_eq!(imm.rex, "a => y".parse().unwrap());
+
+        let caps: CapacitiesBlock = "caps { 3 a }".parse().unwrap();
+        let labels: PropBlock = r#"vis { labels: { a: "Input" } }"#.parse().unwrap();
+
+        assert!(matches!(&blocks[0], CesFileBlock::Caps(block) if block == &caps));
+        assert!(matches!(&blocks[1], CesFileBlock::Vis(block) if block == &labels));
+    }
+
+    #[test]
+    fn test_template_arg_type_mismatch() {
+        let template: TemplateDef =
+            "ces Buffer(x: Dot, cap: Size) { x => y caps { cap x } }".parse().unwrap();
+        let instance: CesInstance = "Buffer!(a, b)".parse().unwrap();
+
+        assert!(template.instantiate(&instance, |_| false).is_err());
+    }
+
+    #[test]
+    fn test_size_arg_undeclared() {
+        let phrase = "ces Buffer(x: Dot, cap: Dot) { x => y caps { cap x } }";
+
+        assert!(phrase.parse::<TemplateDef>().is_err());
+
+        let (_, err) = CesFile::from_script_recovering("caps { a b } ces Main { b => c }").unwrap();
+        let codes: Vec<_> = err
+            .unwrap()
+            .to_diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.get_code().unwrap().to_owned())
+            .collect();
+
+        assert_eq!(codes, vec!["E0308"]);
+    }
+
+    #[test]
+    fn test_template_in_file() {
+        let script = r#"
+ces Arrow(x: Dot, y: Dot) { x => y }
+
+vis { labels: { a: "Source", z: "Sink" } }
+caps { 3 a }
+
+ces Main { Arrow!(a, z) }
+"#;
+        assert!(CesFile::from_script(script).is_ok());
+    }
+}
@@ -0,0 +1,68 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+/// Runs the `ascesis` binary with `args`, passing `input` to its
+/// standard input.
+fn run(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ascesis"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_exit_codes() {
+    assert_eq!(run(&["check", "-"], "ces Main { a => b }").status.code(), Some(0));
+    assert_eq!(run(&["check", "-"], "ces Main { a => }").status.code(), Some(1));
+    assert_eq!(run(&["check", "-"], "ces Main { Missing() }").status.code(), Some(1));
+    assert_eq!(run(&["check", "missing.ces"], "").status.code(), Some(2));
+    assert_eq!(run(&["check", "--bad-flag", "-"], "").status.code(), Some(2));
+
+    let output = run(&["--error-format", "json", "check", "-"], "ces Main { Missing() }");
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\"E0210\""));
+}
+
+#[test]
+fn test_fmt_check() {
+    let formatted = "ces Main { a => b }\n";
+    let output = run(&["fmt", "--check", "-"], formatted);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    let output = run(&["fmt", "--check", "-"], "ces   Main{a=>b}");
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "- needs reformatting\n");
+
+    let output = run(&["fmt", "-"], "ces   Main{a=>b}");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), formatted);
+}
+
+#[test]
+fn test_parse() {
+    let script = "ces Main { a => b }\ncaps { 2 a }\n";
+    let output = run(&["parse", "-"], script);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), script);
+
+    let output = run(&["parse", "--ast", "-"], script);
+    let ast = String::from_utf8_lossy(&output.stdout);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(ast.starts_with("Imm(\n    ImmediateDef {"), "{}", ast);
+    assert!(ast.contains("Caps(\n    CapacitiesBlock {"), "{}", ast);
+}